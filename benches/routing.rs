@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use simple_http::{
+    request::Request,
+    router::Router,
+    service::{Command, Param, Service, System},
+};
+
+type Data = ();
+
+fn system(_req: &mut Request, _data: &Data) -> Command<Data> {
+    Command::None
+}
+
+/// Route table of `resources` top level services, each with a collection, an item collecting an
+/// id and a few actions below the item
+fn tree(resources: usize) -> Service<Data> {
+    let mut root = Service::root();
+
+    for r in 0..resources {
+        let mut resource = Service::with_system(format!("resource{}", r), System::single(system));
+
+        let mut item = Service::with_system("item", System::single(system))
+            .insert_param(Param::CollectExact("id".to_string(), 1));
+
+        for action in ["edit", "history", "comments", "attachments"] {
+            item.insert_child(Service::with_system(action, System::single(system)));
+        }
+
+        resource.insert_child(item);
+        resource.insert_child(
+            Service::with_system("files", System::single(system))
+                .insert_param(Param::CollectAll("path".to_string())),
+        );
+
+        root.insert_child(resource);
+    }
+
+    root
+}
+
+/// Systems and url values of a url
+type Walked<'s> = (Vec<&'s System<Data>>, HashMap<String, Vec<String>>);
+
+/// Segment by segment walk of the service tree, the matching strategy used before `Router`
+/// compiled trees into a radix tree
+fn walk<'s>(root: &'s Service<Data>, url: &str) -> Option<Walked<'s>> {
+    let path = url.split_once('?').map_or(url, |(path, _)| path);
+
+    let mut url_values = HashMap::<String, Vec<String>>::new();
+    let mut systems = Vec::new();
+
+    let mut cur_node = root;
+    let mut segment_iter = path.split_terminator('/').skip(1);
+
+    loop {
+        match cur_node.param() {
+            Param::CollectExact(name, amount) => {
+                let collected = segment_iter
+                    .by_ref()
+                    .take(*amount)
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>();
+
+                if collected.len() < *amount {
+                    systems.extend(cur_node.systems().as_ref());
+                    break;
+                }
+
+                url_values.insert(name.to_owned(), collected);
+            }
+            Param::CollectMaybe(name, amount) => {
+                let collected = segment_iter
+                    .by_ref()
+                    .take(*amount)
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>();
+
+                url_values.insert(name.to_owned(), collected);
+            }
+            Param::CollectAll(name) => {
+                let collected = segment_iter.by_ref().map(|s| s.to_string()).collect();
+
+                url_values.insert(name.to_owned(), collected);
+            }
+            Param::None => {}
+        }
+
+        systems.extend(cur_node.systems().as_ref());
+
+        let Some(segment) = segment_iter.next() else {
+            break;
+        };
+
+        cur_node = cur_node.get_child(segment)?;
+    }
+
+    Some((systems, url_values))
+}
+
+const URLS: [(&str, &str); 5] = [
+    ("static", "/resource7"),
+    ("param", "/resource7/item/42"),
+    ("deep", "/resource7/item/42/attachments?page=2"),
+    ("catch_all", "/resource7/files/docs/2024/report.pdf"),
+    ("missing", "/resource7/item/42/missing"),
+];
+
+fn routing(c: &mut Criterion) {
+    for resources in [10, 1000] {
+        let root = tree(resources);
+        let router = Router::new(tree(resources));
+
+        let mut group = c.benchmark_group(format!("route_{}", resources));
+
+        for (name, url) in URLS {
+            group.bench_with_input(BenchmarkId::new("walk", name), url, |b, url| {
+                b.iter(|| walk(&root, black_box(url)).map(|(systems, _)| systems.len()))
+            });
+
+            group.bench_with_input(BenchmarkId::new("radix", name), url, |b, url| {
+                b.iter(|| {
+                    router
+                        .route(black_box(url))
+                        .map(|route| route.systems().count())
+                })
+            });
+        }
+
+        group.finish();
+    }
+}
+
+fn building(c: &mut Criterion) {
+    c.bench_function("compile_1000", |b| b.iter(|| Router::new(tree(1000))));
+}
+
+criterion_group!(benches, routing, building);
+criterion_main!(benches);
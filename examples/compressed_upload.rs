@@ -0,0 +1,46 @@
+use simple_http::{
+    application::Application,
+    compression::{Decompression, Encoding},
+    request::Request,
+    response::Response,
+    service::{Command, Service, System},
+};
+
+type Data = ();
+
+// Uploads may be compressed, try
+// `echo '{"hello": "world"}' | gzip | curl --data-binary @- -H 'Content-Encoding: gzip' localhost:22555`.
+// Bodies decoding to more than 1 MiB are rejected, try
+// `head -c 2M /dev/zero | gzip | curl --data-binary @- -H 'Content-Encoding: gzip' localhost:22555`.
+
+fn decompress(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let decompression = Decompression::new()
+        .with_encodings(vec![Encoding::Gzip, Encoding::Deflate, Encoding::Brotli])
+        .with_max_size(1024 * 1024);
+
+    match decompression.apply(req) {
+        Ok(()) => Command::None,
+        Err(response) => Command::Respond(response),
+    }
+}
+
+fn upload(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let mut body = Vec::new();
+
+    // Invalid data and bodies exceeding the limit both fail the read
+    if let Err(e) = req.as_reader().read_to_end(&mut body) {
+        return Command::Respond(Response::text(e.to_string()).with_status(400));
+    }
+
+    Command::respond(format!("Received {} bytes\n", body.len()))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().insert_system(System::new(vec![decompress, upload]));
+
+    let app = Application::new("0.0.0.0:22555", root, ())?;
+
+    app.run()?;
+
+    Ok(())
+}
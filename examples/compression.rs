@@ -0,0 +1,63 @@
+use std::{io::Write, thread, time::Duration};
+
+use simple_http::{
+    application::Application,
+    compression::{self, Compression, Encoding},
+    request::Request,
+    response::Response,
+    service::{Command, Service, System},
+};
+
+type Data = ();
+
+// Responses are compressed when the client sends `Accept-Encoding`, try
+// `curl -v --compressed localhost:22555/report` and `curl -N --compressed localhost:22555/live`.
+// `localhost:22555/gzip` only offers gzip and compresses small bodies too.
+
+fn report(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let rows: String = (0..500).map(|i| format!("row {}\n", i)).collect();
+
+    Command::Respond(Response::text(rows))
+}
+
+fn live(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Command::Respond(Response::stream(|writer| {
+        for tick in 0..10 {
+            writeln!(writer, "tick {}", tick)?;
+            writer.flush()?;
+
+            thread::sleep(Duration::from_millis(300));
+        }
+
+        Ok(())
+    }))
+}
+
+fn gzip_only(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Compression::new()
+        .with_encodings(vec![Encoding::Gzip])
+        .with_min_size(0)
+        .apply(req);
+
+    Command::respond("Small but compressed anyway")
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(Service::with_system(
+            "report",
+            System::new(vec![compression::compress, report]),
+        ));
+        s.insert_child(Service::with_system(
+            "live",
+            System::new(vec![compression::compress, live]),
+        ));
+        s.insert_child(Service::with_system("gzip", System::single(gzip_only)));
+    });
+
+    let app = Application::new("0.0.0.0:22555", root, ())?;
+
+    app.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+
+use simple_http::{
+    application::Application,
+    conditional::{self, EntityTag},
+    request::Request,
+    response::Response,
+    service::{Command, Service, System},
+    Method, StatusCode,
+};
+
+type Data = Mutex<(u32, String)>;
+
+// `GET localhost:22555/note` responds with an `ETag` naming the current version of the note,
+// repeating the request with `If-None-Match` responds with `304 Not Modified` until it changes.
+// `PUT` only replaces the note if `If-Match` names the current version, try
+// `curl -X PUT -H 'If-Match: "1"' -d 'Updated' localhost:22555/note`.
+
+fn note(req: &mut Request, ctx: &Data) -> Command<Data> {
+    let mut note = ctx.lock().expect("Note poisoned");
+    let etag = EntityTag::strong(note.0.to_string());
+
+    if *req.method() == Method::Put {
+        // Preconditions of requests changing state must be checked before making the change
+        if let Some(response) = conditional::check(req, Some(&etag), None) {
+            return Command::Respond(response);
+        }
+
+        let Ok(text) = req.body_to_string() else {
+            return Command::respond(StatusCode(400));
+        };
+
+        *note = (note.0 + 1, text);
+    }
+
+    // Preconditions of `GET` requests are evaluated against the `ETag` of the response
+    let etag = EntityTag::strong(note.0.to_string());
+    Command::Respond(Response::text(note.1.clone()).with_etag(&etag))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(Service::with_system("note", System::single(note)));
+    });
+
+    let data = Mutex::new((1, "First note".to_string()));
+
+    let app = Application::new("0.0.0.0:22555", root, data)?;
+
+    app.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use simple_http::{
+    application::Application,
+    cookie::{Cookie, SameSite},
+    cookie_jar::{CookieJar, Key},
+    request::Request,
+    response::Response,
+    service::{Command, Service, System},
+    StatusCode,
+};
+
+type Data = CookieJar;
+
+// Visiting `localhost:22555/visit` counts visits in a cookie, `localhost:22555/forget` removes it.
+// The cookie is signed so the client can read but not modify the count.
+
+fn visit(req: &mut Request, jar: &Data) -> Command<Data> {
+    let visits = req
+        .get_signed_cookie(jar, "visits")
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(0)
+        + 1;
+
+    let cookie = Cookie::new("visits", visits.to_string())
+        .with_path("/")
+        .with_max_age(Duration::from_secs(60 * 60 * 24))
+        .with_http_only(true)
+        .with_same_site(SameSite::Lax);
+
+    Command::Respond(Response::empty(StatusCode(200)).with_cookie(jar.sign(cookie)))
+}
+
+fn forget(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let cookie = Cookie::removal("visits").with_path("/");
+
+    Command::Respond(Response::empty(StatusCode(200)).with_cookie(cookie))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(Service::with_system("visit", System::single(visit)));
+        s.insert_child(Service::with_system("forget", System::single(forget)));
+    });
+
+    // Use `Key::from_secret` with a persistent secret so cookies survive restarts
+    let jar = CookieJar::new(Key::generate());
+
+    let app = Application::new("0.0.0.0:22555", root, jar)?;
+
+    app.run()?;
+
+    Ok(())
+}
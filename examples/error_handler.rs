@@ -0,0 +1,56 @@
+use std::{fs, io};
+
+use simple_http::{
+    application::Application,
+    request::Request,
+    response::Response,
+    service::{Command, Param, Service, System, SystemError},
+    StatusCode,
+};
+
+type Data = ();
+
+// This example should be run from the project root directory using `cargo run --example error_handler`
+
+// Fallible systems return a `Result`, any error can be propagated with `?` and is passed to the
+// application error handler instead of being unwrapped inside the system.
+
+// `localhost:22555/read/random.txt`
+fn read(req: &mut Request, _ctx: &Data) -> Result<Command<Data>, SystemError> {
+    let Some(name) = req.get_url_value("file").and_then(|v| v.first()).cloned() else {
+        return Ok(Command::Respond(Response::empty(StatusCode(404))));
+    };
+
+    if name.contains("..") {
+        return Err("File name may not leave the html folder".into());
+    }
+
+    let contents = fs::read_to_string(format!("examples/html/{}", name))?;
+
+    Ok(Command::Respond(Response::text(contents)))
+}
+
+fn handle_error(req: &mut Request, _ctx: &Data, error: SystemError) -> Response {
+    eprintln!("Failed to respond to {}: {}", req.url(), error);
+
+    match error.downcast_ref::<io::Error>() {
+        Some(e) if e.kind() == io::ErrorKind::NotFound => Response::empty(StatusCode(404)),
+        Some(_) => Response::empty(StatusCode(500)),
+        None => Response::text(error.to_string()).with_status(400),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(
+            Service::with_system("read", System::fallible(read))
+                .insert_param(Param::CollectExact("file".to_string(), 1)),
+        )
+    });
+
+    let app = Application::new("0.0.0.0:22555", root, ())?.with_error_handler(handle_error);
+
+    app.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use simple_http::{
+    application::Application,
+    request::Request,
+    service::{Command, Param, Service, System},
+    StatusCode,
+};
+
+type Data = ();
+
+// Any value implementing `IntoResponse` can be responded with. Returning a `Result` from a helper
+// allows using `?`, the error is responded with just like the success value.
+
+fn parse_numbers(req: &Request) -> Result<Vec<i64>, (StatusCode, String)> {
+    let segments = req.get_url_value("numbers").expect("Param value not found");
+
+    segments
+        .iter()
+        .map(|segment| {
+            segment
+                .parse::<i64>()
+                .map_err(|_| (StatusCode(400), format!("{} is not a number", segment)))
+        })
+        .collect()
+}
+
+fn sum(req: &Request) -> Result<String, (StatusCode, String)> {
+    let numbers = parse_numbers(req)?;
+
+    if numbers.is_empty() {
+        return Err((StatusCode(400), "Nothing to add".to_string()));
+    }
+
+    Ok(numbers.iter().sum::<i64>().to_string())
+}
+
+// `localhost:22555/sum/1/2/3`
+fn sum_system(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Command::respond(sum(req))
+}
+
+// `localhost:22555/teapot`
+fn teapot(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Command::respond((StatusCode(418), [("X-Brew", "tea")], "I'm a teapot"))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(
+            Service::with_system("sum", System::single(sum_system))
+                .insert_param(Param::CollectAll("numbers".to_string())),
+        );
+        s.insert_child(Service::with_system("teapot", System::single(teapot)));
+    });
+
+    let app = Application::new("0.0.0.0:22555", root, ())?;
+
+    app.run()?;
+
+    Ok(())
+}
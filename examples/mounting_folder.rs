@@ -1,46 +1,52 @@
-use std::fs::File;
-
-use simple_http::{service::{Command, Service, System, Param}, request::Request, response::Response, StatusCode, application::Application};
+use simple_http::{
+    application::Application, mime::MimeTypes, service::Service, static_files::StaticFiles,
+};
 
 type Data = ();
 
-// This example should be run from the project root directory using `cargo run --example hello_world`
-
-// A system that returns `None` does not produce a response and simply acts as middleware. Services
-// may have multiple systems and they will always be executed in order. A system that returns
-// `Some(...)` will stop the task and produce a response to the request.
-
-fn root(req: &mut Request, _ctx: &Data) -> Command<Data> {
-    let mut path = std::env::current_dir()
-        .expect("Failed to get working directory")
-        .join("examples/html");
-
-    // This value should always exist. Param values are always inserted
-    let segments = req.get_url_value("file").expect("Param value not found");
+// This example should be run from the project root directory using
+// `cargo run --example mounting_folder`
 
-    for target in segments {
-        path = path.join(target);
-    }
-
-    if path.is_dir() {
-        path = path.join("index.html");
-    }
-
-    let Ok(file) = File::open(path) else {
-        return Command::Respond(Response::empty(StatusCode(404)));
-    };
-
-    Command::Respond(Response::file(file))
-}
+// `StaticFiles` serves the files below a directory. Requests to a directory are answered with
+// its `index.html` and paths trying to leave the directory, like `/static/../Cargo.toml`, are
+// rejected. Try `localhost:22555/static/` and `localhost:22555/static/style.css`, or parts of a
+// file with `curl -H "Range: bytes=0-9,20-29" localhost:22555/static/index.html`.
 
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Root node is equivalent of `/`
-    // You do not need to construct this as `Service::root()`, constructing any other service node
-    // is fine, the name is just ignored.
-    // adding a param will allow you to `get_url_value` from a request, where the next url "segment" is the value
-    let root = Service::root()
-        .insert_system(System::single(root))
-        .insert_param(Param::CollectAll("file".to_string()));
+    // `StaticFiles::service` constructs a service collecting all url segments after its path
+    // and resolving them below the directory.
+    let root = Service::root().fold(|s| {
+        s.insert_child(StaticFiles::new("examples/html").service::<Data>("static"));
+
+        // Directories without an index file are listed, try `localhost:22555/browse/` or
+        // `localhost:22555/browse/?format=json&sort=size&order=desc`. Rust sources are sent as
+        // plain text, with `X-Content-Type-Options: nosniff`.
+        s.insert_child(
+            StaticFiles::new("examples")
+                .with_listing(true)
+                .with_index_files(Vec::new())
+                .with_mime_types(
+                    MimeTypes::new()
+                        .with_type("rs", "text/plain")
+                        .expect("Content type should be valid")
+                        .with_nosniff(true),
+                )
+                .service::<Data>("browse"),
+        );
+
+        // Single page applications handle routing on the client, missing paths are answered with
+        // `index.html` unless they look like a missing asset. Try `localhost:22555/app/any/route`
+        // and `localhost:22555/app/missing.js`.
+        s.insert_child(
+            StaticFiles::new("examples/html")
+                .with_fallback("index.html")
+                .service::<Data>("app"),
+        );
+    });
+
+    // Log the route table before starting
+    println!("{}", root.dump());
 
     let app = Application::new("0.0.0.0:22555", root, ())?;
 
@@ -0,0 +1,53 @@
+use simple_http::{
+    application::Application,
+    request::Request,
+    response::{Redirect, Response},
+    service::{Command, Service, System},
+};
+
+type Data = ();
+
+// `localhost:22555/text`
+fn text(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Command::Respond(Response::text("Hello world"))
+}
+
+// `localhost:22555/html`
+fn html(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Command::Respond(Response::html("<h1>Hello world</h1>"))
+}
+
+// `localhost:22555/old` redirects to `localhost:22555/html`
+fn old(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    Command::Respond(Response::redirect(Redirect::MovedPermanently, "/html"))
+}
+
+// `localhost:22555/created`
+fn created(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    // Header errors are reported once when the response is built instead of on every header
+    let response = Response::builder()
+        .status(201)
+        .header("Location", "/items/1")
+        .header("Content-Type", "application/json")
+        .text("{\"id\": 1}");
+
+    match response {
+        Ok(response) => Command::Respond(response),
+        Err(e) => Command::Respond(Response::text(e.to_string()).with_status(500)),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(Service::with_system("text", System::single(text)));
+        s.insert_child(Service::with_system("html", System::single(html)));
+        s.insert_child(Service::with_system("old", System::single(old)));
+        s.insert_child(Service::with_system("created", System::single(created)));
+    });
+
+    let app = Application::new("0.0.0.0:22555", root, ())?;
+
+    app.run()?;
+
+    Ok(())
+}
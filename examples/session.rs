@@ -0,0 +1,64 @@
+use simple_http::{
+    application::Application,
+    request::Request,
+    response::Response,
+    service::{Command, Service, System},
+    session::{self, MemoryStore, Sessions},
+    StatusCode,
+};
+
+// Sessions are configured once in the application data, `session::load` is added as the first
+// system of any service using sessions. Swap `MemoryStore` for `FileStore` to keep sessions
+// across restarts.
+
+type Data = Sessions;
+
+// `localhost:22555/login/<name>`
+fn login(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let name = req.get_url_value("name").expect("Param value not found")[0].clone();
+
+    let session = req.session_mut().expect("Session not loaded");
+    session.renew();
+    session.insert("name", name);
+
+    Command::Respond(Response::empty(StatusCode(200)))
+}
+
+// `localhost:22555/whoami`
+fn whoami(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let session = req.session().expect("Session not loaded");
+
+    let Some(name) = session.get("name") else {
+        return Command::Respond(Response::empty(StatusCode(401)));
+    };
+
+    println!("Request from {}", name);
+
+    Command::Respond(Response::empty(StatusCode(200)))
+}
+
+// `localhost:22555/logout`
+fn logout(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    req.session_mut().expect("Session not loaded").destroy();
+
+    Command::Respond(Response::empty(StatusCode(200)))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root()
+        .insert_system(System::single(session::load::<Data>))
+        .fold(|s| {
+            s.insert_child(
+                Service::with_param("login", "name".to_string())
+                    .insert_system(System::single(login)),
+            );
+            s.insert_child(Service::with_system("whoami", System::single(whoami)));
+            s.insert_child(Service::with_system("logout", System::single(logout)));
+        });
+
+    let app = Application::new("0.0.0.0:22555", root, Sessions::new(MemoryStore::new()))?;
+
+    app.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::{thread, time::Duration};
+
+use simple_http::{
+    application::Application,
+    request::Request,
+    service::{Command, Service, System},
+    sse::{EventStream, EventStreamService},
+};
+
+type Data = ();
+
+// `localhost:22555/ticks` sends a numbered event every second. Reconnecting clients continue
+// counting from the last event they received. Try `curl -N localhost:22555/ticks`.
+
+struct Ticker {
+    start: u64,
+}
+
+impl EventStreamService<Data> for Ticker {
+    type State = u64;
+
+    fn initial_state(&self) -> Self::State {
+        self.start
+    }
+
+    fn poll_fn(stream: &mut EventStream, _data: &Data, tick: Self::State) -> Option<Self::State> {
+        if tick == 0 {
+            stream.retry(Duration::from_secs(5)).ok()?;
+        }
+
+        let id = tick.to_string();
+        stream
+            .send(Some("tick"), &format!("Tick {}", tick), Some(&id))
+            .ok()?;
+
+        thread::sleep(Duration::from_secs(1));
+
+        Some(tick + 1)
+    }
+}
+
+fn ticks(req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let start = req
+        .last_event_id()
+        .and_then(|id| id.parse::<u64>().ok())
+        .map_or(0, |id| id + 1);
+
+    Command::EventStream(Box::new(Ticker { start }))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(Service::with_system("ticks", System::single(ticks)));
+    });
+
+    let app = Application::new("0.0.0.0:22555", root, ())?;
+
+    app.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::{io::Write, thread, time::Duration};
+
+use simple_http::{
+    application::Application,
+    request::Request,
+    response::Response,
+    service::{Command, Service, System},
+};
+
+type Data = ();
+
+// `localhost:22555/export` streams a csv file row by row, the client receives every row as soon
+// as it is flushed instead of once the whole export is done. Try `curl -N localhost:22555/export`.
+
+fn export(_req: &mut Request, _ctx: &Data) -> Command<Data> {
+    let response = Response::stream(|writer| {
+        writeln!(writer, "id,square")?;
+
+        for id in 0..10 {
+            // Simulate a slow query
+            thread::sleep(Duration::from_millis(200));
+
+            writeln!(writer, "{},{}", id, id * id)?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    })
+    .with_header("Content-Type", "text/csv")
+    .expect("Header should be valid");
+
+    Command::Respond(response)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let root = Service::root().fold(|s| {
+        s.insert_child(Service::with_system("export", System::single(export)));
+    });
+
+    let app = Application::new("0.0.0.0:22555", root, ())?;
+
+    app.run()?;
+
+    Ok(())
+}
@@ -18,6 +18,7 @@ impl WebsocketService<Data> for WsHandler {
 
     fn initial_state(&self) -> Self::State {}
 
+    #[allow(unused_variables)]
     fn poll_fn(ws: &mut Websocket, data: &Data, state: Self::State) -> Option<Self::State> {
         println!("Running");
 
@@ -66,18 +67,19 @@ fn convert_key(input: &str) -> String {
         })
 }
 
+#[allow(clippy::needless_borrow)]
 fn root(req: &mut Request, _ctx: &Data) -> Command<Data> {
     let Some(key) = req.headers().iter().find(|h| h.field.equiv(&"Sec-Websocket-Key")).map(|h| h.value.clone()) else {
         return Command::Respond(Response::empty(StatusCode(400)));
     };
 
-    let response = Response::empty(StatusCode(101))
-        .with_header("Upgrade", "websocket")
-        .unwrap()
-        .with_header("Connection", "Upgrade")
-        .unwrap()
-        .with_header("Sec-Websocket-Accept", &convert_key(key.as_str()))
-        .unwrap();
+    let response = Response::builder()
+        .status(101)
+        .header("Upgrade", "websocket")
+        .header("Connection", "Upgrade")
+        .header("Sec-Websocket-Accept", &convert_key(key.as_str()))
+        .empty()
+        .expect("Handshake headers should be valid");
 
     Command::Upgrade(response, Box::new(WsHandler))
 }
@@ -1,23 +1,42 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use tiny_http::{Server, StatusCode};
 
 use crate::{
     error::Error,
+    request::Request,
     response::Response,
-    service::{Command, Param, Service, System},
-    request::Request, websocket::Websocket,
+    router::Router,
+    service::{Command, Service, System, SystemError},
+    sse::EventStream,
+    websocket::{Websocket, WebsocketServiceExport},
 };
 
+/// Maps errors returned by a `FallibleSystemFn` to a response, see
+/// `Application::with_error_handler`
+pub type ErrorHandlerFn<Data> = fn(&mut Request, &Data, SystemError) -> Response;
+
+/// Error handler used unless replaced, logs the error and responds with `StatusCode(500)`
+pub fn default_error_handler<Data>(
+    req: &mut Request,
+    _data: &Data,
+    error: SystemError,
+) -> Response {
+    log::error!("Error handling request to {}: {}", req.url(), error);
+
+    Response::empty(StatusCode(500))
+}
+
 /// Main application responsible for handling all net requests, resources, threading, and routing
 /// this should be the base of any application made on simple-http
 pub struct Application<Data = ()>
 where
     Data: Send + Sync,
 {
-    root: Arc<Service<Data>>,
+    router: Arc<Router<Data>>,
     server: Server,
     data: Arc<Data>,
+    error_handler: ErrorHandlerFn<Data>,
 }
 
 impl<Data> Application<Data>
@@ -25,9 +44,10 @@ where
     Data: Send + Sync + 'static,
 {
     /// Constructs a new instance of an application given an address structured as: `ip:port`, a
-    /// root node, and some initial data.
+    /// root node, and some initial data. Fails with `Error::InvalidRoutes` if the tree has
+    /// conflicting or unreachable routes, see `Router::try_new`.
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// Application::new("0.0.0.0:80", Node::root(), ())
     /// ```
     pub fn new(
@@ -35,13 +55,39 @@ where
         root: Service<Data>,
         data: Data,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let router = Router::try_new(root).map_err(Error::InvalidRoutes)?;
+
         Ok(Self {
-            root: Arc::new(root),
+            router: Arc::new(router),
             server: Server::http(addr)?,
             data: Arc::new(data),
+            error_handler: default_error_handler,
         })
     }
 
+    /// Replace the handler responsible for mapping errors returned by a `FallibleSystemFn` to a
+    /// response. The handler is also responsible for logging the error.
+    ///
+    /// ```rust,no_run
+    /// # use simple_http::{application::Application, request::Request, response::Response, service::{Service, SystemError}, StatusCode};
+    /// fn handle_error(req: &mut Request, _data: &(), error: SystemError) -> Response {
+    ///     eprintln!("{}: {}", req.url(), error);
+    ///
+    ///     match error.downcast_ref::<std::io::Error>() {
+    ///         Some(e) if e.kind() == std::io::ErrorKind::NotFound => Response::empty(StatusCode(404)),
+    ///         _ => Response::text("Something went wrong").with_status(500),
+    ///     }
+    /// }
+    ///
+    /// let app = Application::new("0.0.0.0:80", Service::root(), ())
+    ///     .map(|app| app.with_error_handler(handle_error));
+    /// ```
+    pub fn with_error_handler(mut self, handler: ErrorHandlerFn<Data>) -> Self {
+        self.error_handler = handler;
+
+        self
+    }
+
     /// Initialize main application loop. This method is blocking and will only return on close or
     /// error
     pub fn run(self) -> Result<(), Error> {
@@ -50,98 +96,90 @@ where
                 return Err(Error::ServerClosed);
             };
 
-            let root_clone = self.root.clone();
+            let router_clone = self.router.clone();
             let context_clone = self.data.clone();
+            let error_handler = self.error_handler;
 
             std::thread::spawn(move || {
-                let mut url_values = HashMap::<String, Vec<String>>::new();
-                let mut services = Vec::<&System<Data>>::new();
-
-                let mut cur_node = root_clone.as_ref();
+                let Some(route) = router_clone.route(tiny_request.url()) else {
+                    Response::empty(StatusCode(404)).send(tiny_request);
 
-                let mut segment_iter = tiny_request.url().split_terminator("/").skip(1);
+                    return;
+                };
 
-                'segment_iter: loop {
-                    if let Some(callback) = cur_node.systems() {
-                        services.push(callback)
-                    }
-
-                    match cur_node.param() {
-                        Param::CollectExact(name, amount) => {
-                            let mut collected_segments = Vec::new();
-                            for _ in 0..*amount {
-                                let Some(segment) = segment_iter.next() else {
-                                    break 'segment_iter;
-                                };
-
-                                collected_segments.push(segment.to_string());
-                            }
-
-                            url_values.insert(name.to_owned(), collected_segments);
-                        }
-                        Param::CollectMaybe(name, amount) => {
-                            let mut collected_segments = Vec::new();
-                            for _ in 0..*amount {
-                                let Some(segment) = segment_iter.next() else {
-                                   break;
-                                };
-
-                                collected_segments.push(segment.to_string());
-                            }
-
-                            url_values.insert(name.to_owned(), collected_segments);
-                        }
-                        Param::CollectAll(name) => {
-                            let collected_segments =
-                                segment_iter.map(|s| s.to_string()).collect::<Vec<String>>();
-                            url_values.insert(name.to_owned(), collected_segments);
-
-                            break 'segment_iter;
-                        }
-                        Param::None => {}
-                    }
+                let (systems, url_values) = route.into_parts();
+                let mut request = Request::from_request(
+                    &mut tiny_request,
+                    url_values,
+                    router_clone.urls().clone(),
+                );
 
-                    let Some(segment) = segment_iter.next() else {
-                        break 'segment_iter;
-                    };
+                let dispatched = dispatch(&systems, &mut request, &context_clone, error_handler);
 
-                    let Some(child) = cur_node.get_child(segment) else {
-                        let response = Response::empty(StatusCode(404));
+                // The request borrows the body reader of `tiny_request`
+                drop(request);
 
-                        let _ = tiny_request.respond(response.into());
+                match dispatched {
+                    Dispatch::Respond(response) => response.send(tiny_request),
+                    Dispatch::Upgrade(response, websocket_service) => {
+                        let ws = tiny_request.upgrade("websocket", response.into());
 
-                        return;
-                    };
+                        let ws = Websocket::new(ws);
 
-                    cur_node = child;
+                        websocket_service.run(context_clone, ws);
+                    }
                 }
+            });
+        }
+    }
+}
 
-                let mut request = Request::from_request(&mut tiny_request, url_values);
-
-                for service in services {
-                    let command = service.call(&mut request, context_clone.as_ref());
-
-                    match command {
-                        Command::Respond(response) => {
-                            let _ = tiny_request.respond(response.into());
-                            return;
-                        }
-                        Command::Upgrade(response, websocket_service) => {
-                            drop(request);
-
-                            let ws = tiny_request.upgrade("websocket", response.into());
-
-                            let ws = Websocket::new(ws);
+/// Result of running the systems of a request, see `dispatch`
+pub(crate) enum Dispatch<Data> {
+    /// Response to send, already passed through `Request::finish`
+    Respond(Response),
 
-                            websocket_service.run(context_clone, ws);
-                            return;
-                        }
-                        Command::None => continue,
-                    }
-                }
+    /// Upgrade the connection to a websocket after sending the response
+    Upgrade(Response, Box<dyn WebsocketServiceExport<Data>>),
+}
 
-                let _ = tiny_request.respond(Response::empty(StatusCode(500)).into());
-            });
+/// Runs `systems` in order until one produces a command, mapping errors with `error_handler`.
+/// Event streams are started on their own thread and answered with their response.
+pub(crate) fn dispatch<Data>(
+    systems: &[&System<Data>],
+    request: &mut Request,
+    data: &Arc<Data>,
+    error_handler: ErrorHandlerFn<Data>,
+) -> Dispatch<Data>
+where
+    Data: Send + Sync + 'static,
+{
+    for system in systems {
+        let command = match system.try_call(request, data.as_ref()) {
+            Ok(command) => command,
+            Err(e) => {
+                let response = error_handler(request, data.as_ref(), e);
+
+                return Dispatch::Respond(request.finish(response));
+            }
+        };
+
+        match command {
+            Command::Respond(response) => return Dispatch::Respond(request.finish(response)),
+            Command::Upgrade(response, websocket_service) => {
+                return Dispatch::Upgrade(response, websocket_service)
+            }
+            Command::EventStream(event_stream_service) => {
+                let (stream, response) = EventStream::new();
+                let response = request.finish(response);
+
+                event_stream_service.run(data.clone(), stream);
+
+                return Dispatch::Respond(response);
+            }
+            Command::None => continue,
         }
     }
+
+    Dispatch::Respond(Response::empty(StatusCode(500)))
 }
@@ -0,0 +1,544 @@
+use std::io::{self, Read, Write};
+
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+    Compression as Level,
+};
+use tiny_http::{Method, StatusCode};
+
+use crate::{
+    conditional,
+    request::Request,
+    response::{Body, Response},
+    service::Command,
+    static_files::accept_encoding,
+    stream::StreamWriter,
+};
+
+/// Content codings supported for response compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// Value of the `Content-Encoding` header
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Wraps `writer` in an encoder writing compressed data to it
+    fn encoder<W: Write + Send + 'static>(&self, writer: W) -> Box<dyn Write + Send> {
+        match self {
+            Encoding::Brotli => Box::new(brotli::CompressorWriter::new(writer, 4096, 5, 22)),
+            Encoding::Gzip => Box::new(GzEncoder::new(writer, Level::default())),
+            Encoding::Deflate => Box::new(ZlibEncoder::new(writer, Level::default())),
+        }
+    }
+}
+
+/// Parses an `Accept-Encoding` header and returns the preferred encoding among `supported`.
+/// Encodings with equal quality are preferred in the order of `supported`.
+///
+/// ```rust
+/// # use simple_http::compression::{negotiate, Encoding::*};
+/// let supported = [Brotli, Gzip, Deflate];
+/// let best = |header: &str| negotiate(header, &supported);
+///
+/// assert_eq!(best("gzip, deflate"), Some(Gzip));
+/// assert_eq!(best("gzip;q=0.5, br"), Some(Brotli));
+/// assert_eq!(best("GZIP;q=0.8, deflate;q=0.9"), Some(Deflate));
+/// assert_eq!(best("x-gzip"), Some(Gzip));
+/// assert_eq!(best("*"), Some(Brotli));
+///
+/// // `q=0` rules an encoding out, also when a wildcard would accept it
+/// assert_eq!(best("br;q=0, gzip"), Some(Gzip));
+/// assert_eq!(best("br;q=0, *"), Some(Gzip));
+/// assert_eq!(best("gzip;q=0"), None);
+/// assert_eq!(best("*;q=0"), None);
+/// assert_eq!(best("identity"), None);
+/// assert_eq!(best(""), None);
+/// ```
+pub fn negotiate(accept_encoding: &str, supported: &[Encoding]) -> Option<Encoding> {
+    let mut wildcard = None;
+    let mut qualities = Vec::new();
+
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name == "*" {
+            wildcard = Some(quality);
+        } else {
+            qualities.push((name, quality));
+        }
+    }
+
+    supported
+        .iter()
+        .filter_map(|encoding| {
+            let quality = qualities
+                .iter()
+                .find(|(name, _)| {
+                    name == encoding.name() || (name == "x-gzip" && *encoding == Encoding::Gzip)
+                })
+                .map(|(_, quality)| *quality)
+                .or(wildcard)?;
+
+            (quality > 0.0).then_some((*encoding, quality))
+        })
+        .fold(
+            None,
+            |best: Option<(Encoding, f32)>, (encoding, quality)| match best {
+                Some((_, best_quality)) if best_quality >= quality => best,
+                _ => Some((encoding, quality)),
+            },
+        )
+        .map(|(encoding, _)| encoding)
+}
+
+/// Response compression configuration. Compression is opt-in, add `compress` or a system
+/// calling `Compression::apply` before the systems producing responses.
+///
+/// Responses are compressed when the client accepts one of the enabled encodings, unless they
+/// are already encoded, have a content type that is already compressed or a known length below
+/// the minimum size. Streaming responses are compressed as they are produced, every flush of the
+/// stream still reaches the client.
+///
+/// A strong `ETag` of a compressed response is made weak, it identified the uncompressed bytes.
+/// Conditional requests still match with the weak tag, `If-Match` and `If-Range` don't.
+///
+/// ```rust
+/// # use simple_http::{compression::compress, conditional::EntityTag, request::Request, response::Response, service::{Command, Service, System}, testing::TestClient};
+/// fn page(_req: &mut Request, _data: &()) -> Command<()> {
+///     let response = Response::text("Hello world ".repeat(200)).with_etag(&EntityTag::strong("v1"));
+///
+///     Command::respond(response)
+/// }
+///
+/// let client = TestClient::new(
+///     Service::with_system("root", System::single(compress).insert(page)),
+///     (),
+/// );
+///
+/// let response = client.get("/").header("Accept-Encoding", "gzip").send().unwrap();
+/// assert_eq!(response.get_header("Content-Encoding"), Some("gzip"));
+/// assert_eq!(response.get_header("ETag"), Some("W/\"v1\""));
+///
+/// let response = client.get("/").send().unwrap();
+/// assert_eq!(response.get_header("ETag"), Some("\"v1\""));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compression {
+    encodings: Vec<Encoding>,
+    min_size: usize,
+    skipped_types: Vec<String>,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            encodings: vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate],
+            min_size: 1024,
+            skipped_types: [
+                "image/",
+                "video/",
+                "audio/",
+                "font/woff",
+                "application/zip",
+                "application/gzip",
+                "application/x-gzip",
+                "application/x-bzip2",
+                "application/x-xz",
+                "application/x-7z-compressed",
+                "application/x-rar-compressed",
+                "application/zstd",
+            ]
+            .iter()
+            .map(|t| t.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl Compression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enabled encodings in order of preference when the client accepts several equally
+    pub fn with_encodings(mut self, encodings: Vec<Encoding>) -> Self {
+        self.encodings = encodings;
+
+        self
+    }
+
+    /// Bodies with a known length below `min_size` bytes are sent uncompressed
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+
+        self
+    }
+
+    /// Skip compression of content types starting with `content_type`
+    pub fn with_skipped_type(mut self, content_type: impl Into<String>) -> Self {
+        self.skipped_types.push(content_type.into());
+
+        self
+    }
+
+    /// Register a response hook on `req` compressing the response it produces
+    pub fn apply(&self, req: &mut Request) {
+        if *req.method() == Method::Head {
+            return;
+        }
+
+        let encoding = accept_encoding(req).and_then(|value| negotiate(value, &self.encodings));
+
+        let config = self.clone();
+        req.on_response(move |_, response| config.compress(response, encoding));
+    }
+
+    fn compress(&self, response: Response, encoding: Option<Encoding>) -> Response {
+        let status = response.status_code().0;
+        if status < 200 || status == 204 || status == 206 || status == 304 {
+            return response;
+        }
+
+        if response.get_header("Content-Encoding").is_some() {
+            return response;
+        }
+
+        let content_type = response
+            .get_header("Content-Type")
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        let precompressed = self
+            .skipped_types
+            .iter()
+            .any(|t| content_type.starts_with(t.as_str()))
+            && !content_type.starts_with("image/svg");
+
+        if precompressed || response.body_length().is_some_and(|l| l < self.min_size) {
+            return response;
+        }
+
+        let response = add_vary(response);
+
+        let Some(encoding) = encoding else {
+            return response;
+        };
+
+        // Ranges of the encoded body can't be served, see `range::apply`
+        conditional::weaken_etag(response)
+            .without_header("Content-Length")
+            .without_header("Accept-Ranges")
+            .with_valid_header("Content-Encoding", encoding.name())
+            .map_body(|body| encode_body(body, encoding))
+    }
+}
+
+/// System compressing responses with the default `Compression` configuration
+pub fn compress<Data>(req: &mut Request, _data: &Data) -> Command<Data> {
+    Compression::default().apply(req);
+
+    Command::None
+}
+
+/// Adds `Accept-Encoding` to the `Vary` header unless already present
+pub(crate) fn add_vary(response: Response) -> Response {
+    let Some(vary) = response.get_header("Vary") else {
+        return response.with_valid_header("Vary", "Accept-Encoding");
+    };
+
+    let varies = vary
+        .split(',')
+        .any(|v| v.trim() == "*" || v.trim().eq_ignore_ascii_case("Accept-Encoding"));
+
+    if varies {
+        return response;
+    }
+
+    let vary = format!("{}, Accept-Encoding", vary);
+
+    response
+        .without_header("Vary")
+        .with_valid_header("Vary", &vary)
+}
+
+fn encode_body(body: Body, encoding: Encoding) -> Body {
+    match body {
+        Body::Empty => Body::Empty,
+        Body::Bytes(bytes) => match compress_bytes(&bytes, encoding) {
+            Ok(compressed) => Body::Bytes(compressed),
+            Err(_) => Body::Bytes(bytes),
+        },
+        Body::Static(bytes) => match compress_bytes(bytes, encoding) {
+            Ok(compressed) => Body::Bytes(compressed),
+            Err(_) => Body::Static(bytes),
+        },
+        Body::File(file) => encode_reader(Box::new(file), encoding),
+        Body::Reader(reader, _) => encode_reader(reader, encoding),
+        Body::Stream(receiver) => {
+            let (writer, compressed) = StreamWriter::new();
+
+            std::thread::spawn(move || {
+                let abort = writer.sender();
+                let mut encoder = encoding.encoder(writer);
+
+                for chunk in receiver.iter() {
+                    // The compressed stream is aborted with the original one
+                    let chunk = match chunk {
+                        Ok(chunk) => chunk,
+                        Err(e) => {
+                            let _ = abort.send(Err(e));
+
+                            return;
+                        }
+                    };
+
+                    // Flushing after every chunk keeps the stream interactive
+                    if encoder
+                        .write_all(&chunk)
+                        .and_then(|_| encoder.flush())
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            });
+
+            Body::Stream(compressed)
+        }
+    }
+}
+
+/// Compresses a reader on its own thread while the response is sent
+fn encode_reader(mut reader: Box<dyn Read + Send>, encoding: Encoding) -> Body {
+    let (writer, compressed) = StreamWriter::new();
+
+    std::thread::spawn(move || {
+        let abort = writer.sender();
+        let mut encoder = encoding.encoder(writer);
+
+        if let Err(e) = io::copy(&mut reader, &mut encoder) {
+            let _ = abort.send(Err(e));
+        }
+    });
+
+    Body::Stream(compressed)
+}
+
+fn compress_bytes(bytes: &[u8], encoding: Encoding) -> io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            encoder.write_all(bytes)?;
+
+            Ok(encoder.into_inner())
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+            encoder.write_all(bytes)?;
+
+            encoder.finish()
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Level::default());
+            encoder.write_all(bytes)?;
+
+            encoder.finish()
+        }
+    }
+}
+
+/// Request body decompression configuration. Decompression is opt-in, add `decompress` or a
+/// system calling `Decompression::apply` before the systems reading the body.
+///
+/// Once applied, `Request::as_reader` and the body helpers return the decoded body. Reading
+/// fails with `io::ErrorKind::InvalidData` once the decoded body exceeds the size limit, which
+/// protects against small bodies expanding to huge sizes.
+///
+/// ```rust
+/// # use std::io::{ErrorKind, Write};
+/// # use flate2::{write::GzEncoder, Compression};
+/// # use simple_http::{compression::Decompression, request::Request, response::Response, service::{Command, Service, System}, testing::TestClient, StatusCode};
+/// fn echo(req: &mut Request, _data: &()) -> Command<()> {
+///     if let Err(response) = Decompression::new().with_max_size(16).apply(req) {
+///         return Command::Respond(response);
+///     }
+///
+///     match req.body_to_string() {
+///         Ok(body) => Command::respond(body),
+///         Err(e) if e.kind() == ErrorKind::InvalidData => {
+///             Command::Respond(Response::empty(StatusCode(413)))
+///         }
+///         Err(_) => Command::Respond(Response::empty(StatusCode(400))),
+///     }
+/// }
+///
+/// let client = TestClient::new(Service::with_system("root", System::single(echo)), ());
+///
+/// let gzip = |body: &str| {
+///     let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+///     encoder.write_all(body.as_bytes()).unwrap();
+///     encoder.finish().unwrap()
+/// };
+///
+/// let send = |encoding: &str, body: Vec<u8>| {
+///     let mut response = client.post("/").header("Content-Encoding", encoding).body(body).send().unwrap();
+///     (response.status_code().0, response.body_to_string().unwrap())
+/// };
+///
+/// assert_eq!(send("gzip", gzip("hello")), (200, "hello".into()));
+/// assert_eq!(send("identity", b"plain".to_vec()), (200, "plain".into()));
+///
+/// // The limit applies to the decoded body
+/// assert_eq!(send("gzip", gzip(&"a".repeat(16))), (200, "a".repeat(16)));
+/// assert_eq!(send("gzip", gzip(&"a".repeat(17))), (413, "".into()));
+///
+/// // Encodings that are not enabled are rejected
+/// let response = client.post("/").header("Content-Encoding", "br").body("...").send().unwrap();
+/// assert_eq!(response.status_code(), StatusCode(415));
+/// assert_eq!(response.get_header("Accept-Encoding"), Some("gzip, deflate"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Decompression {
+    encodings: Vec<Encoding>,
+    max_size: usize,
+}
+
+impl Default for Decompression {
+    fn default() -> Self {
+        Self {
+            encodings: vec![Encoding::Gzip, Encoding::Deflate],
+            max_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl Decompression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encodings accepted in the `Content-Encoding` header of requests
+    pub fn with_encodings(mut self, encodings: Vec<Encoding>) -> Self {
+        self.encodings = encodings;
+
+        self
+    }
+
+    /// Maximum size of a decoded body in bytes
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+
+        self
+    }
+
+    /// Decode the body of `req` according to its `Content-Encoding` header. Bodies with an
+    /// encoding that is not enabled are rejected with a `StatusCode(415)` response listing the
+    /// accepted encodings.
+    pub fn apply(&self, req: &mut Request) -> Result<(), Response> {
+        let Some(content_encoding) = req
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Content-Encoding"))
+            .map(|h| h.value.as_str().trim().to_ascii_lowercase())
+        else {
+            return Ok(());
+        };
+
+        if content_encoding.is_empty() || content_encoding == "identity" {
+            return Ok(());
+        }
+
+        let encoding = self.encodings.iter().find(|encoding| {
+            content_encoding == encoding.name()
+                || (content_encoding == "x-gzip" && **encoding == Encoding::Gzip)
+        });
+
+        let Some(encoding) = encoding else {
+            let accepted = self
+                .encodings
+                .iter()
+                .map(|encoding| encoding.name())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let response =
+                Response::empty(StatusCode(415)).with_valid_header("Accept-Encoding", &accepted);
+
+            return Err(response);
+        };
+
+        req.set_decoder(*encoding, self.max_size);
+
+        Ok(())
+    }
+}
+
+/// System decoding request bodies with the default `Decompression` configuration
+pub fn decompress<Data>(req: &mut Request, _data: &Data) -> Command<Data> {
+    match Decompression::default().apply(req) {
+        Ok(()) => Command::None,
+        Err(response) => Command::Respond(response),
+    }
+}
+
+/// Decodes a request body read from `source`. Decoded data is produced as it is read, and
+/// reading fails as soon as more than `max_size` bytes come out of the decoder, so a small body
+/// never expands beyond the limit in memory.
+pub(crate) struct BodyDecoder<'a> {
+    decoder: io::Take<Box<dyn Read + 'a>>,
+    max_size: usize,
+}
+
+impl<'a> BodyDecoder<'a> {
+    /// Buffer size of the brotli decoder
+    const BUFFER_SIZE: usize = 4096;
+
+    pub(crate) fn new(encoding: Encoding, max_size: usize, source: Box<dyn Read + 'a>) -> Self {
+        let decoder: Box<dyn Read + 'a> = match encoding {
+            Encoding::Brotli => Box::new(brotli::Decompressor::new(source, Self::BUFFER_SIZE)),
+            Encoding::Gzip => Box::new(GzDecoder::new(source)),
+            Encoding::Deflate => Box::new(ZlibDecoder::new(source)),
+        };
+
+        Self {
+            // One byte more than the limit tells a body of exactly `max_size` bytes apart from
+            // a larger one
+            decoder: decoder.take(max_size as u64 + 1),
+            max_size,
+        }
+    }
+}
+
+impl Read for BodyDecoder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.decoder.read(buf)?;
+
+        let decoded = self.max_size as u64 + 1 - self.decoder.limit();
+        if decoded > self.max_size as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Decoded request body exceeds the size limit",
+            ));
+        }
+
+        Ok(read)
+    }
+}
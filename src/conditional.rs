@@ -0,0 +1,271 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tiny_http::{Method, StatusCode};
+
+use crate::{request::Request, response::Response};
+
+/// Headers of a response kept in a `StatusCode(304)` response replacing it
+const NOT_MODIFIED_HEADERS: [&str; 7] = [
+    "Cache-Control",
+    "Content-Location",
+    "Date",
+    "ETag",
+    "Expires",
+    "Last-Modified",
+    "Vary",
+];
+
+/// Validator identifying a version of a response body, sent in the `ETag` header. Weak tags
+/// identify semantically equivalent bodies, strong tags identical bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    weak: bool,
+    tag: String,
+}
+
+impl EntityTag {
+    /// Constructs a strong tag. `tag` may not contain `"` or control characters, these are
+    /// removed.
+    pub fn strong(tag: impl Into<String>) -> Self {
+        Self::new(false, tag.into())
+    }
+
+    /// Constructs a weak tag, see `strong`
+    pub fn weak(tag: impl Into<String>) -> Self {
+        Self::new(true, tag.into())
+    }
+
+    fn new(weak: bool, mut tag: String) -> Self {
+        tag.retain(|c| c != '"' && !c.is_control());
+
+        Self { weak, tag }
+    }
+
+    /// Weak tag derived from the size and modification time of a file
+    pub(crate) fn from_metadata(length: u64, modified: SystemTime) -> Self {
+        let modified = modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        Self::weak(format!("{:x}-{:x}", length, modified))
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Both tags are strong and identical, required for `If-Match` and `If-Range`
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Tags are identical regardless of weakness, used for `If-None-Match`
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+
+    /// Parses a single tag such as `"abc"` or `W/"abc"`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (weak, quoted) = match value.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, value),
+        };
+
+        let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+        if tag.contains('"') {
+            return None;
+        }
+
+        Some(Self::new(weak, tag.to_string()))
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// Value of an `If-Match` or `If-None-Match` header
+enum TagList {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl TagList {
+    fn parse(value: &str) -> Self {
+        if value.trim() == "*" {
+            return TagList::Any;
+        }
+
+        // Tags may contain commas, split after every closing quote instead
+        let mut tags = Vec::new();
+        let mut rest = value;
+        while let Some(start) = rest.find('"') {
+            let Some(end) = rest[start + 1..].find('"') else {
+                break;
+            };
+
+            let end = start + 1 + end;
+            let weak = rest[..start].trim_end_matches([' ', ',']).ends_with("W/");
+            tags.push(EntityTag::new(weak, rest[start + 1..end].to_string()));
+
+            rest = &rest[end + 1..];
+        }
+
+        TagList::Tags(tags)
+    }
+
+    fn matches(
+        &self,
+        etag: Option<&EntityTag>,
+        compare: fn(&EntityTag, &EntityTag) -> bool,
+    ) -> bool {
+        match (self, etag) {
+            (TagList::Any, etag) => etag.is_some(),
+            (TagList::Tags(tags), Some(etag)) => tags.iter().any(|tag| compare(tag, etag)),
+            (TagList::Tags(_), None) => false,
+        }
+    }
+}
+
+/// Evaluate the preconditions of `req` against the current validators of the targeted resource.
+/// Returns the response to send instead of processing the request, `StatusCode(304)` for `GET`
+/// and `HEAD` requests whose cached response is still valid and `StatusCode(412)` for failed
+/// preconditions.
+///
+/// Systems changing state should call this before making any change, e.g. to reject a `PUT` with
+/// an outdated `If-Match` header. Preconditions of `GET` and `HEAD` requests are evaluated
+/// automatically for responses carrying an `ETag` or `Last-Modified` header, see `apply`.
+///
+/// ```rust
+/// # use simple_http::{conditional::{self, EntityTag}, request::Request, service::Command};
+/// fn update(req: &mut Request, _data: &()) -> Command<()> {
+///     let current = EntityTag::strong("v2");
+///
+///     if let Some(response) = conditional::check(req, Some(&current), None) {
+///         return Command::Respond(response);
+///     }
+///
+///     // Apply the update
+///     Command::respond("Updated")
+/// }
+/// ```
+pub fn check(
+    req: &Request,
+    etag: Option<&EntityTag>,
+    last_modified: Option<SystemTime>,
+) -> Option<Response> {
+    let header = |name: &'static str| {
+        req.headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str())
+    };
+
+    let last_modified = last_modified.map(truncate);
+    let date =
+        |name: &'static str| header(name).and_then(|value| httpdate::parse_http_date(value).ok());
+
+    // Evaluated in the order defined by RFC 9110 section 13.2.2
+    if let Some(if_match) = header("If-Match") {
+        if !TagList::parse(if_match).matches(etag, EntityTag::strong_eq) {
+            return Some(Response::empty(StatusCode(412)));
+        }
+    } else if let (Some(since), Some(last_modified)) = (date("If-Unmodified-Since"), last_modified)
+    {
+        if last_modified > since {
+            return Some(Response::empty(StatusCode(412)));
+        }
+    }
+
+    let safe = matches!(req.method(), Method::Get | Method::Head);
+
+    if let Some(if_none_match) = header("If-None-Match") {
+        if TagList::parse(if_none_match).matches(etag, EntityTag::weak_eq) {
+            let status = if safe { 304 } else { 412 };
+
+            return Some(Response::empty(StatusCode(status)));
+        }
+    } else if let (true, Some(since), Some(last_modified)) =
+        (safe, date("If-Modified-Since"), last_modified)
+    {
+        if last_modified <= since {
+            return Some(Response::empty(StatusCode(304)));
+        }
+    }
+
+    None
+}
+
+/// Evaluate the preconditions of a `GET` or `HEAD` request against the validators of
+/// `response`, replacing successful responses with `StatusCode(304)` or `StatusCode(412)` as
+/// described in `check`. This runs automatically for every response.
+pub fn apply(req: &Request, response: Response) -> Response {
+    let status = response.status_code().0;
+    if !(200..300).contains(&status) || !matches!(req.method(), Method::Get | Method::Head) {
+        return response;
+    }
+
+    let (etag, last_modified) = validators(&response);
+
+    let Some(replacement) = check(req, etag.as_ref(), last_modified) else {
+        return response;
+    };
+
+    if replacement.status_code().0 != 304 {
+        return replacement;
+    }
+
+    // Caches update their stored response with the headers of a 304
+    response
+        .headers()
+        .iter()
+        .filter(|h| {
+            NOT_MODIFIED_HEADERS
+                .iter()
+                .any(|name| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        })
+        .fold(replacement, |replacement, header| {
+            replacement.with_typed_header(header.clone())
+        })
+}
+
+/// The `ETag` and `Last-Modified` validators of `response`
+pub(crate) fn validators(response: &Response) -> (Option<EntityTag>, Option<SystemTime>) {
+    let etag = response.get_header("ETag").and_then(EntityTag::parse);
+    let last_modified = response
+        .get_header("Last-Modified")
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+
+    (etag, last_modified)
+}
+
+/// Replaces a strong `ETag` of `response` with a weak one. Used once the body is encoded, the
+/// strong tag identified the bytes of the unencoded body.
+pub(crate) fn weaken_etag(response: Response) -> Response {
+    match validators(&response) {
+        (Some(etag), _) if !etag.is_weak() => response.with_etag(&EntityTag::weak(etag.tag())),
+        _ => response,
+    }
+}
+
+/// HTTP dates have a precision of one second
+pub(crate) fn truncate(time: SystemTime) -> SystemTime {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs())
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{Duration, SystemTime},
+};
+
+use crate::percent::{decode, encode};
+
+/// Value of the `SameSite` cookie attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl Display for SameSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// A single http cookie. Formatting a cookie with `Display` produces the value of a `Set-Cookie`
+/// header, see `Response::with_cookie`.
+///
+/// Names and values are percent-encoded when they contain characters that are not allowed in a
+/// cookie, and decoded again when read through `Request::get_cookie`.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use simple_http::{cookie::{Cookie, SameSite}, request::Request, response::Response, service::{Command, Service, System}, testing::TestClient};
+/// let cookie = Cookie::new("greeting", "hello world; Secure")
+///     .with_path("/app; Domain=evil.example")
+///     .with_max_age(Duration::from_secs(60))
+///     .with_http_only(true)
+///     .with_same_site(SameSite::None);
+///
+/// // Separators can't inject attributes, `SameSite=None` implies `Secure`
+/// assert_eq!(
+///     cookie.to_string(),
+///     "greeting=hello%20world%3B%20Secure; Path=/app%3B Domain=evil.example; Max-Age=60; \
+///      Secure; HttpOnly; SameSite=None"
+/// );
+///
+/// assert_eq!(
+///     Cookie::removal("greeting").to_string(),
+///     "greeting=; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT"
+/// );
+///
+/// fn echo(req: &mut Request, _data: &()) -> Command<()> {
+///     let greeting = req.get_cookie("greeting").unwrap_or_default().to_string();
+///
+///     Command::respond(Response::text(greeting).with_cookie(Cookie::removal("greeting")))
+/// }
+///
+/// let client = TestClient::new(Service::with_system("root", System::single(echo)), ());
+///
+/// // Values are decoded, quotes are removed and the first of duplicate cookies wins
+/// let mut response = client
+///     .get("/")
+///     .header("Cookie", "greeting=\"hello%20world%3B%20Secure\"; greeting=other")
+///     .send()
+///     .unwrap();
+/// assert_eq!(response.body_to_string().unwrap(), "hello world; Secure");
+/// assert!(response.get_header("Set-Cookie").unwrap().starts_with("greeting=; Max-Age=0"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    partitioned: bool,
+}
+
+impl Cookie {
+    /// Constructs a cookie with no attributes
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            partitioned: false,
+        }
+    }
+
+    /// Constructs a cookie instructing the client to remove the cookie `name`. The `Path` and
+    /// `Domain` of the removal cookie must match those of the original cookie.
+    pub fn removal(name: impl Into<String>) -> Self {
+        Self::new(name, "")
+            .with_max_age(Duration::ZERO)
+            .with_expires(SystemTime::UNIX_EPOCH)
+    }
+
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.value = value.into();
+
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+
+        self
+    }
+
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+
+        self
+    }
+
+    /// `Max-Age` is sent in whole seconds
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+
+        self
+    }
+
+    pub fn with_expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+
+        self
+    }
+
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+
+        self
+    }
+
+    /// `SameSite=None` cookies are always sent with `Secure`, browsers reject them otherwise.
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+
+        self
+    }
+
+    /// Partitioned cookies are always sent with `Secure`, browsers reject them otherwise.
+    pub fn with_partitioned(mut self, partitioned: bool) -> Self {
+        self.partitioned = partitioned;
+
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure || self.partitioned || self.same_site == Some(SameSite::None)
+    }
+
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+
+    pub fn partitioned(&self) -> bool {
+        self.partitioned
+    }
+}
+
+impl Display for Cookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}={}",
+            encode(&self.name, is_token_byte),
+            encode(&self.value, is_cookie_octet)
+        )?;
+
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", encode(path, is_attribute_byte))?;
+        }
+
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", encode(domain, is_attribute_byte))?;
+        }
+
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", httpdate::fmt_http_date(expires))?;
+        }
+
+        if self.secure() {
+            write!(f, "; Secure")?;
+        }
+
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+
+        if self.partitioned {
+            write!(f, "; Partitioned")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the value of a `Cookie` request header into `cookies`. Cookies already present in the
+/// map are kept, browsers send the most specific cookie first.
+pub(crate) fn parse_cookie_header(header: &str, cookies: &mut HashMap<String, String>) {
+    for pair in header.split(';') {
+        let Some((name, value)) = pair.trim().split_once('=') else {
+            continue;
+        };
+
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+
+        cookies.entry(decode(name)).or_insert_with(|| decode(value));
+    }
+}
+
+/// Token characters allowed in a cookie name (RFC 7230)
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$&'*+-.^_`|~".contains(&byte)
+}
+
+/// Characters allowed in a cookie value (RFC 6265)
+fn is_cookie_octet(byte: u8) -> bool {
+    matches!(byte, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+/// Characters allowed in `Path` and `Domain` attribute values (RFC 6265)
+fn is_attribute_byte(byte: u8) -> bool {
+    matches!(byte, 0x20..=0x7E) && byte != b';'
+}
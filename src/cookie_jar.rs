@@ -0,0 +1,191 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::cookie::Cookie;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Length of the AES-GCM nonce prepended to encrypted values
+const NONCE_LEN: usize = 12;
+
+/// Secret used by a `CookieJar` to sign and encrypt cookies. Separate keys are derived for
+/// signing and encryption.
+#[derive(Clone)]
+pub struct Key {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl Key {
+    /// Derives a key from an application secret. The secret should be at least 32 random bytes,
+    /// and must stay the same across restarts for previously issued cookies to remain valid.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            signing: derive(secret, b"simple_http cookie signing"),
+            encryption: derive(secret, b"simple_http cookie encryption"),
+        }
+    }
+
+    /// Generates a random key. Cookies issued with this key are invalidated on restart.
+    pub fn generate() -> Self {
+        Self::from_secret(&Aes256Gcm::generate_key(OsRng))
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+fn derive(secret: &[u8], label: &[u8]) -> [u8; 32] {
+    let mut mac = hmac(secret);
+    mac.update(label);
+
+    mac.finalize().into_bytes().into()
+}
+
+/// Signs or encrypts cookie values so they can't be tampered with by the client.
+///
+/// Signed cookies are readable by the client but any modification is detected, encrypted
+/// cookies are also hidden from the client. Both are bound to the name of the cookie.
+///
+/// New cookies are always issued with the first key, all other keys are only accepted when
+/// reading. This allows rotating keys without invalidating the cookies of every client.
+///
+/// ```rust
+/// # use simple_http::{cookie::Cookie, cookie_jar::{CookieJar, Key}};
+/// let old = Key::from_secret(b"an old secret of at least 32 bytes..");
+/// let new = Key::from_secret(b"a new secret of at least 32 bytes...");
+///
+/// let jar = CookieJar::new(old.clone());
+/// let signed = jar.sign(Cookie::new("user", "42"));
+/// let encrypted = jar.encrypt(Cookie::new("cart", "3 apples"));
+///
+/// assert!(signed.value().ends_with(".42"));
+/// assert_eq!(jar.verify("user", signed.value()).as_deref(), Some("42"));
+/// assert_eq!(jar.decrypt("cart", encrypted.value()).as_deref(), Some("3 apples"));
+///
+/// // Tampered values and values moved to another cookie are rejected
+/// let tampered = signed.value().replace(".42", ".43");
+/// assert_eq!(jar.verify("user", &tampered), None);
+/// assert_eq!(jar.verify("admin", signed.value()), None);
+/// assert_eq!(jar.decrypt("user", encrypted.value()), None);
+/// assert_eq!(jar.decrypt("cart", &encrypted.value()[1..]), None);
+///
+/// // After rotating, old cookies are still accepted and new ones are issued with the new key
+/// let rotated = CookieJar::new(new.clone()).with_key(old);
+/// assert_eq!(rotated.verify("user", signed.value()).as_deref(), Some("42"));
+/// assert_eq!(rotated.decrypt("cart", encrypted.value()).as_deref(), Some("3 apples"));
+///
+/// let reissued = rotated.sign(Cookie::new("user", "42"));
+/// assert_eq!(CookieJar::new(new).verify("user", reissued.value()).as_deref(), Some("42"));
+/// assert_eq!(jar.verify("user", reissued.value()), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    keys: Vec<Key>,
+}
+
+impl CookieJar {
+    /// Constructs a jar issuing and accepting cookies with `key`
+    pub fn new(key: Key) -> Self {
+        Self { keys: vec![key] }
+    }
+
+    /// Accept cookies issued with an older `key` when reading
+    pub fn with_key(mut self, key: Key) -> Self {
+        self.keys.push(key);
+
+        self
+    }
+
+    /// Replaces the value of `cookie` with a signed value
+    pub fn sign(&self, cookie: Cookie) -> Cookie {
+        let tag = URL_SAFE_NO_PAD.encode(mac(&self.keys[0], cookie.name(), cookie.value()));
+        let value = format!("{}.{}", tag, cookie.value());
+
+        cookie.with_value(value)
+    }
+
+    /// Returns the original value of a signed cookie value if the signature is valid for any key
+    pub fn verify(&self, name: &str, value: &str) -> Option<String> {
+        let (tag, value) = value.split_once('.')?;
+        let tag = URL_SAFE_NO_PAD.decode(tag).ok()?;
+
+        self.keys.iter().find_map(|key| {
+            let mut mac = hmac(&key.signing);
+            update_mac(&mut mac, name, value);
+
+            mac.verify_slice(&tag).ok().map(|_| value.to_string())
+        })
+    }
+
+    /// Replaces the value of `cookie` with an encrypted value
+    pub fn encrypt(&self, cookie: Cookie) -> Cookie {
+        let cipher = Aes256Gcm::new(&self.keys[0].encryption.into());
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+
+        let payload = Payload {
+            msg: cookie.value().as_bytes(),
+            aad: cookie.name().as_bytes(),
+        };
+
+        let mut data = nonce.to_vec();
+        data.extend(
+            cipher
+                .encrypt(&nonce, payload)
+                .expect("Encrypting a cookie value can't exceed AES-GCM limits"),
+        );
+
+        let value = URL_SAFE_NO_PAD.encode(data);
+
+        cookie.with_value(value)
+    }
+
+    /// Returns the original value of an encrypted cookie value if it decrypts with any key
+    pub fn decrypt(&self, name: &str, value: &str) -> Option<String> {
+        let data = URL_SAFE_NO_PAD.decode(value).ok()?;
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let nonce = Nonce::from_slice(nonce);
+
+        self.keys.iter().find_map(|key| {
+            let cipher = Aes256Gcm::new(&key.encryption.into());
+            let payload = Payload {
+                msg: ciphertext,
+                aad: name.as_bytes(),
+            };
+
+            let plaintext = cipher.decrypt(nonce, payload).ok()?;
+
+            String::from_utf8(plaintext).ok()
+        })
+    }
+}
+
+fn hmac(key: &[u8]) -> HmacSha256 {
+    <HmacSha256 as Mac>::new_from_slice(key).expect("Hmac accepts keys of any length")
+}
+
+fn mac(key: &Key, name: &str, value: &str) -> Vec<u8> {
+    let mut mac = hmac(&key.signing);
+    update_mac(&mut mac, name, value);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The name is included so a signed value can't be moved to another cookie
+fn update_mac(mac: &mut HmacSha256, name: &str, value: &str) {
+    mac.update(&(name.len() as u64).to_be_bytes());
+    mac.update(name.as_bytes());
+    mac.update(value.as_bytes());
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use flate2::{write::GzEncoder, Compression as Level};
+use sha2::{Digest, Sha256};
+use tiny_http::StatusCode;
+
+use crate::{
+    compression::{self, Encoding},
+    conditional::{self, EntityTag},
+    mime, range,
+    request::Request,
+    response::Response,
+    service::Service,
+    static_files::{
+        accept_encoding, directory_redirect, files_service, method_not_allowed, path_segments,
+    },
+};
+
+/// File compiled into the binary, usually generated by `generate` in a build script
+#[derive(Debug, Clone, Copy)]
+pub struct Asset {
+    path: &'static str,
+    bytes: &'static [u8],
+    content_type: &'static str,
+    etag: &'static str,
+    gzip: Option<&'static [u8]>,
+    brotli: Option<&'static [u8]>,
+}
+
+impl Asset {
+    /// Constructs an asset served at `path`, relative to the service path and starting with a
+    /// `/`. `etag` is used as a strong `ETag` and must change whenever `bytes` changes.
+    pub const fn new(
+        path: &'static str,
+        bytes: &'static [u8],
+        content_type: &'static str,
+        etag: &'static str,
+    ) -> Self {
+        Self {
+            path,
+            bytes,
+            content_type,
+            etag,
+            gzip: None,
+            brotli: None,
+        }
+    }
+
+    /// Gzip compressed `bytes`, served to clients accepting gzip
+    pub const fn with_gzip(mut self, gzip: &'static [u8]) -> Self {
+        self.gzip = Some(gzip);
+
+        self
+    }
+
+    /// Brotli compressed `bytes`, served to clients accepting br
+    pub const fn with_brotli(mut self, brotli: &'static [u8]) -> Self {
+        self.brotli = Some(brotli);
+
+        self
+    }
+
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    /// The body and tag of the variant of this asset in `encoding`
+    fn variant(&self, encoding: Option<Encoding>) -> Option<(&'static [u8], EntityTag)> {
+        let (bytes, suffix) = match encoding {
+            None => return Some((self.bytes, EntityTag::strong(self.etag))),
+            Some(Encoding::Brotli) => (self.brotli?, "br"),
+            Some(Encoding::Gzip) => (self.gzip?, "gz"),
+            Some(Encoding::Deflate) => return None,
+        };
+
+        // Strong tags must differ between encodings of the same asset
+        Some((
+            bytes,
+            EntityTag::strong(format!("{}-{}", self.etag, suffix)),
+        ))
+    }
+}
+
+/// Serves assets compiled into the binary, with the same support for conditional and range
+/// requests as `StaticFiles`. Precompressed variants of assets are served to clients accepting
+/// them.
+///
+/// In `build.rs`, with `simple_http` as a build dependency:
+///
+/// ```rust,no_run
+/// let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("assets.rs");
+/// simple_http::embedded::generate("public", out).expect("Failed to embed assets");
+/// ```
+///
+/// Then in the application:
+///
+/// ```rust,ignore
+/// static ASSETS: &[Asset] = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+///
+/// let root = Service::root().fold(|s| {
+///     s.insert_child(EmbeddedFiles::new(ASSETS).service("assets"));
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct EmbeddedFiles {
+    assets: HashMap<&'static str, &'static Asset>,
+    index_files: Vec<String>,
+    precompressed: bool,
+}
+
+impl EmbeddedFiles {
+    /// Constructs a service configuration serving `assets`, with `index.html` as index file
+    pub fn new(assets: &'static [Asset]) -> Self {
+        Self {
+            assets: assets.iter().map(|asset| (asset.path, asset)).collect(),
+            index_files: vec!["index.html".to_string()],
+            precompressed: true,
+        }
+    }
+
+    /// Files served for requests to a directory, the first existing file is used
+    pub fn with_index_files(mut self, index_files: Vec<String>) -> Self {
+        self.index_files = index_files;
+
+        self
+    }
+
+    /// Serve the precompressed variants of assets, enabled by default
+    pub fn with_precompressed(mut self, precompressed: bool) -> Self {
+        self.precompressed = precompressed;
+
+        self
+    }
+
+    /// Constructs a service at `path` serving the assets below it
+    pub fn service<Data: 'static>(self, path: impl Into<String>) -> Service<Data> {
+        files_service(path, move |req, segments| self.serve(req, segments))
+    }
+
+    /// Respond to `req` with the asset at the path made of the url `segments`, for use in custom
+    /// systems.
+    pub fn serve(&self, req: &Request, segments: &[String]) -> Response {
+        if let Some(response) = method_not_allowed(req) {
+            return response;
+        }
+
+        let path = path_segments(segments)
+            .iter()
+            .fold(String::new(), |path, segment| path + "/" + segment);
+
+        let asset = match self.assets.get(path.as_str()) {
+            Some(asset) => asset,
+            None => {
+                let directory = path.trim_end_matches('/');
+                let index = self
+                    .index_files
+                    .iter()
+                    .find_map(|index| self.assets.get(format!("{}/{}", directory, index).as_str()));
+
+                let Some(index) = index else {
+                    return Response::empty(StatusCode(404));
+                };
+
+                if let Some(redirect) = directory_redirect(req) {
+                    return redirect;
+                }
+
+                index
+            }
+        };
+
+        let encoding = self.negotiate(req, asset);
+        let Some((bytes, etag)) = asset.variant(encoding) else {
+            return Response::empty(StatusCode(500));
+        };
+
+        let mut response = Response::from_static(bytes)
+            .with_valid_header("Content-Type", asset.content_type)
+            .with_etag(&etag);
+
+        if let Some(encoding) = encoding {
+            response = response.with_valid_header("Content-Encoding", encoding.name());
+        }
+
+        if asset.gzip.is_some() || asset.brotli.is_some() {
+            response = compression::add_vary(response);
+        }
+
+        range::apply(req, conditional::apply(req, response))
+    }
+
+    /// The best precompressed variant of `asset` accepted by the client
+    fn negotiate(&self, req: &Request, asset: &Asset) -> Option<Encoding> {
+        if !self.precompressed {
+            return None;
+        }
+
+        let accept_encoding = accept_encoding(req)?;
+
+        let available = [
+            asset.brotli.map(|_| Encoding::Brotli),
+            asset.gzip.map(|_| Encoding::Gzip),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        compression::negotiate(accept_encoding, &available)
+    }
+}
+
+/// Generates the asset table of all files below `source` for use with `include!`, see
+/// `EmbeddedFiles`. Meant to be called from a build script, hidden files are skipped.
+///
+/// Compressible assets are precompressed with gzip and brotli into the directory of
+/// `destination`, variants are only kept if they are smaller than the asset. The build script
+/// is rerun whenever a file below `source` changes.
+pub fn generate(source: impl AsRef<Path>, destination: impl AsRef<Path>) -> io::Result<()> {
+    let source = fs::canonicalize(source)?;
+    let destination = destination.as_ref();
+    let variants = destination.with_extension("variants");
+
+    let mut files = Vec::new();
+    collect_files(&source, &mut files)?;
+    files.sort();
+
+    let mut table = String::from("&[\n");
+
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+
+        let relative = file
+            .strip_prefix(&source)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let path = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .fold(String::new(), |path, segment| path + "/" + &segment);
+
+        let bytes = fs::read(&file)?;
+        let etag = Sha256::digest(&bytes)[..16]
+            .iter()
+            .fold(String::new(), |mut etag, byte| {
+                let _ = write!(etag, "{:02x}", byte);
+                etag
+            });
+
+        let content_type = mime::built_in().content_type(&file);
+
+        let _ = write!(
+            table,
+            "    simple_http::embedded::Asset::new({:?}, include_bytes!({:?}), {:?}, {:?})",
+            path, file, content_type, etag
+        );
+
+        if compressible(&content_type) {
+            let variant = variants.join(relative);
+            if let Some(parent) = variant.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut gzip = GzEncoder::new(Vec::new(), Level::best());
+            gzip.write_all(&bytes)?;
+            let gzip = gzip.finish()?;
+
+            let mut brotli = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+            brotli.write_all(&bytes)?;
+            let brotli = brotli.into_inner();
+
+            for (compressed, extension, method) in
+                [(gzip, "gz", "with_gzip"), (brotli, "br", "with_brotli")]
+            {
+                if compressed.len() >= bytes.len() {
+                    continue;
+                }
+
+                let mut variant = variant.clone().into_os_string();
+                variant.push(".");
+                variant.push(extension);
+
+                fs::write(&variant, compressed)?;
+
+                let _ = write!(
+                    table,
+                    "\n        .{}(include_bytes!({:?}))",
+                    method, variant
+                );
+            }
+        }
+
+        table.push_str(",\n");
+    }
+
+    table.push_str("]\n");
+
+    println!("cargo:rerun-if-changed={}", source.display());
+
+    fs::write(destination, table)
+}
+
+/// Recursively collects the paths of all non-hidden files below `directory`
+fn collect_files(directory: &Path, files: &mut Vec<std::path::PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Content types worth precompressing
+fn compressible(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.starts_with("image/svg")
+        || ["application/json", "application/xml", "application/wasm"]
+            .iter()
+            .any(|t| content_type.starts_with(t))
+        || content_type == "image/x-icon"
+}
@@ -3,6 +3,9 @@ pub enum Error {
     FailedToInitializeRuntime,
     ServerClosed,
     Io(std::io::Error),
+
+    /// The service tree has routes that conflict or can never be reached, see `Router::try_new`
+    InvalidRoutes(Vec<RouteError>),
 }
 
 impl std::fmt::Display for Error {
@@ -13,8 +16,139 @@ impl std::fmt::Display for Error {
             FailedToInitializeRuntime => write!(f, "Failed to initialize runtime"),
             ServerClosed => write!(f, "Server closed"),
             Io(e) => e.fmt(f),
+            InvalidRoutes(errors) => {
+                write!(f, "Invalid routes:")?;
+
+                for e in errors {
+                    write!(f, "\n  {}", e)?;
+                }
+
+                Ok(())
+            }
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+/// Describes why a header could not be added to a `Response`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    /// Header names must be non empty and only contain token characters
+    InvalidName(String),
+
+    /// Header values must be ascii and may not contain control characters such as line breaks
+    InvalidValue { name: String, value: String },
+}
+
+impl std::fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use HeaderError::*;
+
+        match self {
+            InvalidName(name) => write!(f, "Invalid header name {:?}", name),
+            InvalidValue { name, value } => {
+                write!(f, "Invalid value {:?} for header {:?}", value, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// Describes why a route could not be added to a `RadixTree` or a `Router`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// Catch-all segments must be the last segment of a pattern
+    InvalidPattern(String),
+
+    /// A pattern differing at most in parameter names was already added
+    Conflict { pattern: String, existing: String },
+
+    /// A child was inserted at a path already taken by a sibling, replacing it
+    DuplicatePath(String),
+
+    /// No url reaches the service at this pattern
+    Unreachable {
+        pattern: String,
+        reason: &'static str,
+    },
+
+    /// More than one service uses this name, see `Service::insert_name`
+    DuplicateName(String),
+
+    /// Two services merged at this pattern set conflicting values, see `Service::merge`
+    MergeConflict {
+        pattern: String,
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for RouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use RouteError::*;
+
+        match self {
+            InvalidPattern(pattern) => write!(f, "Invalid route pattern {:?}", pattern),
+            Conflict { pattern, existing } => {
+                write!(f, "Route {:?} conflicts with {:?}", pattern, existing)
+            }
+            DuplicatePath(pattern) => {
+                write!(f, "Route {:?} was inserted more than once", pattern)
+            }
+            Unreachable { pattern, reason } => {
+                write!(f, "Route {:?} can never be reached, {}", pattern, reason)
+            }
+            DuplicateName(name) => write!(f, "Route name {:?} is used more than once", name),
+            MergeConflict { pattern, reason } => {
+                write!(f, "Route {:?} can not be merged, {}", pattern, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// Describes why a url could not be built, see `UrlTable::url_for`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// No service has this name
+    UnknownRoute(String),
+
+    /// The route needs more values for a parameter than were given
+    MissingValues { route: String, name: String },
+
+    /// More values were given for a parameter than the route collects, or values for a
+    /// parameter the route does not have
+    UnexpectedValues { route: String, name: String },
+
+    /// A value is empty, `.` or `..`, segments clients and servers drop or resolve
+    InvalidValue {
+        route: String,
+        name: String,
+        value: String,
+    },
+}
+
+impl std::fmt::Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use UrlError::*;
+
+        match self {
+            UnknownRoute(route) => write!(f, "No route named {:?}", route),
+            MissingValues { route, name } => {
+                write!(f, "Route {:?} is missing values for {:?}", route, name)
+            }
+            UnexpectedValues { route, name } => {
+                write!(f, "Route {:?} does not take values for {:?}", route, name)
+            }
+            InvalidValue { route, name, value } => write!(
+                f,
+                "Route {:?} can not take {:?} as a value for {:?}",
+                route, value, name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
@@ -1,10 +1,27 @@
 pub mod application;
+pub mod compression;
+pub mod conditional;
+pub mod cookie;
+pub mod cookie_jar;
+pub mod embedded;
 pub mod error;
+pub mod mime;
+pub mod radix;
+pub mod range;
 pub mod request;
 pub mod response;
+pub mod router;
 pub mod service;
+pub mod session;
+pub mod sse;
+pub mod static_files;
+pub mod stream;
+pub mod testing;
+pub mod urls;
 pub mod websocket;
 
+mod percent;
+
 pub use tiny_http::Header;
 pub use tiny_http::Method;
-pub use tiny_http::StatusCode;
\ No newline at end of file
+pub use tiny_http::StatusCode;
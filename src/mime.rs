@@ -0,0 +1,179 @@
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use crate::{
+    error::HeaderError,
+    response::{make_header, Response},
+};
+
+/// Content type of files without a known extension
+pub const DEFAULT: &str = "application/octet-stream";
+
+/// Built-in extension to content type mapping, see `MimeTypes`
+const BUILT_IN: [(&str, &str); 43] = [
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("md", "text/markdown"),
+    ("ics", "text/calendar"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("xml", "application/xml"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+    ("weba", "audio/webm"),
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mov", "video/quicktime"),
+    ("vtt", "text/vtt"),
+];
+
+/// Maps file extensions to content types, starting from a built-in table of common web types.
+/// Text types are sent with a charset, utf-8 unless changed.
+///
+/// ```rust
+/// # use std::path::Path;
+/// # use simple_http::mime::MimeTypes;
+/// let types = MimeTypes::new()
+///     .with_type("glb", "model/gltf-binary")?
+///     .with_type("txt", "text/plain; charset=iso-8859-1")?;
+///
+/// assert_eq!(types.content_type(Path::new("scene.glb")), "model/gltf-binary");
+/// assert_eq!(types.content_type(Path::new("index.HTML")), "text/html; charset=utf-8");
+///
+/// // Types must be valid header values
+/// assert!(MimeTypes::new().with_type("txt", "text/plain; charset=ütf-8").is_err());
+/// assert!(MimeTypes::new().with_charset(Some("utf-8\r\nX: y".to_string())).is_err());
+/// # Ok::<(), simple_http::error::HeaderError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MimeTypes {
+    types: HashMap<String, String>,
+    charset: Option<String>,
+    nosniff: bool,
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        Self {
+            types: BUILT_IN
+                .iter()
+                .map(|(extension, content_type)| (extension.to_string(), content_type.to_string()))
+                .collect(),
+            charset: Some("utf-8".to_string()),
+            nosniff: false,
+        }
+    }
+}
+
+impl MimeTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the content type of files with `extension`, compared case-insensitively.
+    /// Fails if `content_type` is not a valid `Content-Type` header value.
+    pub fn with_type(
+        mut self,
+        extension: &str,
+        content_type: impl Into<String>,
+    ) -> Result<Self, HeaderError> {
+        let content_type = content_type.into();
+        make_header("Content-Type", &content_type)?;
+
+        self.types
+            .insert(extension.to_ascii_lowercase(), content_type);
+
+        Ok(self)
+    }
+
+    /// Charset added to `text/*` types without one, `None` to send text types without a charset.
+    /// Fails if `charset` can't be part of a `Content-Type` header value.
+    pub fn with_charset(mut self, charset: Option<String>) -> Result<Self, HeaderError> {
+        if let Some(charset) = &charset {
+            make_header("Content-Type", &format!("text/plain; charset={}", charset))?;
+        }
+
+        self.charset = charset;
+
+        Ok(self)
+    }
+
+    /// Send `X-Content-Type-Options: nosniff` with typed responses so browsers never guess a
+    /// different content type, e.g. execute a text file as a script
+    pub fn with_nosniff(mut self, nosniff: bool) -> Self {
+        self.nosniff = nosniff;
+
+        self
+    }
+
+    /// Content type of the file at `path`, `DEFAULT` for unknown extensions
+    pub fn content_type(&self, path: &Path) -> String {
+        let content_type = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.types.get(&extension.to_ascii_lowercase()));
+
+        let Some(content_type) = content_type else {
+            return DEFAULT.to_string();
+        };
+
+        match &self.charset {
+            Some(charset) if content_type.starts_with("text/") && !content_type.contains(';') => {
+                format!("{}; charset={}", content_type, charset)
+            }
+            _ => content_type.clone(),
+        }
+    }
+
+    /// Set the `Content-Type` of `response` to the type of the file at `path`
+    pub fn apply(&self, path: &Path, response: Response) -> Response {
+        // Types and charsets are validated when added, the combination is valid as well
+        let response = match make_header("Content-Type", &self.content_type(path)) {
+            Ok(header) => response.with_typed_header(header),
+            Err(_) => response,
+        };
+
+        if !self.nosniff {
+            return response;
+        }
+
+        response
+            .without_header("X-Content-Type-Options")
+            .with_valid_header("X-Content-Type-Options", "nosniff")
+    }
+}
+
+/// The built-in types, used by `Response::open`
+pub(crate) fn built_in() -> &'static MimeTypes {
+    static BUILT_IN_TYPES: OnceLock<MimeTypes> = OnceLock::new();
+
+    BUILT_IN_TYPES.get_or_init(MimeTypes::default)
+}
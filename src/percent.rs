@@ -0,0 +1,49 @@
+use std::fmt::Write;
+
+/// Percent-encodes every byte not accepted by `allowed`. `%` is always encoded so decoding is
+/// unambiguous.
+pub(crate) fn encode(input: &str, allowed: fn(u8) -> bool) -> String {
+    let mut output = String::with_capacity(input.len());
+
+    for byte in input.bytes() {
+        if allowed(byte) && byte != b'%' {
+            output.push(byte as char);
+        } else {
+            let _ = write!(output, "%{:02X}", byte);
+        }
+    }
+
+    output
+}
+
+/// Reverses `encode`, invalid escapes are left untouched.
+pub(crate) fn decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| input.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                output.push(byte);
+                i += 3;
+            }
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(output).unwrap_or_else(|_| input.to_string())
+}
+
+/// Characters never encoded in a path segment or query component, RFC 3986 section 2.3
+pub(crate) fn is_unreserved_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
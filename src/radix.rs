@@ -0,0 +1,366 @@
+use crate::error::RouteError;
+
+/// Part of a route pattern between two `/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Matches exactly this segment
+    Static(String),
+
+    /// Matches any single segment, possibly empty
+    Param(String),
+
+    /// Matches all remaining segments, possibly none. Must be the last segment of a pattern.
+    CatchAll(String),
+}
+
+impl Segment {
+    /// Parses a segment of a pattern, `:name` for parameters and `*name` for catch-alls
+    pub fn parse(segment: &str) -> Self {
+        if let Some(name) = segment.strip_prefix(':') {
+            Segment::Param(name.to_string())
+        } else if let Some(name) = segment.strip_prefix('*') {
+            Segment::CatchAll(name.to_string())
+        } else {
+            Segment::Static(segment.to_string())
+        }
+    }
+}
+
+/// Compressed radix tree matching paths against route patterns such as `/users/:id` and
+/// `/files/*path`. Nodes share common prefixes of static segments, so matching a path only
+/// compares each of its bytes once per candidate branch.
+///
+/// Static segments take precedence over parameters, parameters over catch-alls. A branch that
+/// fails to match further down is abandoned for the next candidate, so `/users/me/posts` falls
+/// back to `/users/:id/posts` if `/users/me` has no `posts` below it. A trailing `/` is ignored
+/// in both patterns and paths.
+///
+/// ```rust
+/// # use simple_http::radix::RadixTree;
+/// let mut tree = RadixTree::new();
+/// tree.insert("/users/me", "me").unwrap();
+/// tree.insert("/users/:id", "user").unwrap();
+/// tree.insert("/files/*path", "file").unwrap();
+///
+/// let found = tree.at("/users/42").unwrap();
+/// assert_eq!(*found.value(), "user");
+/// assert_eq!(found.params(), [("id", "42")]);
+///
+/// assert_eq!(*tree.at("/users/me").unwrap().value(), "me");
+/// assert_eq!(tree.at("/files/css/site.css").unwrap().params(), [("path", "css/site.css")]);
+/// assert!(tree.at("/posts").is_none());
+///
+/// assert!(tree.insert("/users/:name", "other").is_err());
+/// ```
+pub struct RadixTree<T> {
+    root: Node<T>,
+}
+
+impl<T> RadixTree<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Node::new(String::new()),
+        }
+    }
+
+    /// Insert `value` at `pattern`, a path whose segments are parsed with `Segment::parse`.
+    /// Fails if a pattern differing only in parameter names was already inserted.
+    pub fn insert(&mut self, pattern: &str, value: T) -> Result<(), RouteError> {
+        let pattern = pattern.strip_suffix('/').unwrap_or(pattern);
+
+        let segments = pattern
+            .split('/')
+            .skip(1)
+            .map(Segment::parse)
+            .collect::<Vec<_>>();
+
+        self.insert_segments(&segments, value)
+    }
+
+    /// Insert `value` at the pattern made of `segments`, see `insert`. Static segments are
+    /// matched literally, even if they start with `:` or `*`.
+    pub fn insert_segments(&mut self, segments: &[Segment], value: T) -> Result<(), RouteError> {
+        let pattern = render(segments);
+
+        let mut names = Vec::new();
+        let mut node = &mut self.root;
+        let mut prefix = String::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            match segment {
+                Segment::Static(segment) => {
+                    prefix.push('/');
+                    prefix.push_str(segment);
+                }
+                Segment::Param(name) => {
+                    prefix.push('/');
+                    node = node.static_node(&prefix);
+                    prefix.clear();
+
+                    names.push(name.clone());
+                    node = node
+                        .param
+                        .get_or_insert_with(|| Box::new(Node::new(String::new())));
+                }
+                Segment::CatchAll(name) => {
+                    if i + 1 != segments.len() {
+                        return Err(RouteError::InvalidPattern(pattern));
+                    }
+
+                    node = node.static_node(&prefix);
+                    if let Some(existing) = &node.catch_all {
+                        return Err(RouteError::Conflict {
+                            pattern,
+                            existing: existing.pattern.clone(),
+                        });
+                    }
+
+                    names.push(name.clone());
+                    node.catch_all = Some(Leaf {
+                        pattern,
+                        names,
+                        value,
+                    });
+
+                    return Ok(());
+                }
+            }
+        }
+
+        node = node.static_node(&prefix);
+        if let Some(existing) = &node.leaf {
+            return Err(RouteError::Conflict {
+                pattern,
+                existing: existing.pattern.clone(),
+            });
+        }
+
+        node.leaf = Some(Leaf {
+            pattern,
+            names,
+            value,
+        });
+
+        Ok(())
+    }
+
+    /// Find the value of the pattern matching `path`, which should not include a query string
+    pub fn at<'t, 'p>(&'t self, path: &'p str) -> Option<Match<'t, 'p, T>> {
+        let path = path.strip_suffix('/').unwrap_or(path);
+
+        let mut values = Vec::new();
+        let leaf = self.root.find(path, &mut values)?;
+
+        Some(Match {
+            value: &leaf.value,
+            pattern: &leaf.pattern,
+            params: leaf
+                .names
+                .iter()
+                .map(|name| name.as_str())
+                .zip(values)
+                .collect(),
+        })
+    }
+
+    /// Values of all inserted patterns
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        let mut leaves = Vec::new();
+        self.root.collect_leaves(&mut leaves);
+
+        leaves
+            .into_iter()
+            .map(|leaf| (leaf.pattern.as_str(), &leaf.value))
+    }
+}
+
+impl<T> Default for RadixTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Value found by `RadixTree::at` and the parameters captured from the path
+pub struct Match<'t, 'p, T> {
+    value: &'t T,
+    pattern: &'t str,
+    params: Vec<(&'t str, &'p str)>,
+}
+
+impl<'t, 'p, T> Match<'t, 'p, T> {
+    pub fn value(&self) -> &'t T {
+        self.value
+    }
+
+    /// The pattern the value was inserted at, e.g. `/users/:id`
+    pub fn pattern(&self) -> &'t str {
+        self.pattern
+    }
+
+    /// Name and value of every parameter in pattern order. Catch-alls capture the remaining path
+    /// without its leading `/`.
+    pub fn params(&self) -> &[(&'t str, &'p str)] {
+        &self.params
+    }
+
+    /// Get the value of the parameter `name`
+    pub fn get(&self, name: &str) -> Option<&'p str> {
+        self.params
+            .iter()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+struct Leaf<T> {
+    pattern: String,
+    names: Vec<String>,
+    value: T,
+}
+
+struct Node<T> {
+    /// Static bytes matched by this node, empty for parameter nodes
+    prefix: String,
+
+    /// Static children, their prefixes start with distinct characters
+    children: Vec<Node<T>>,
+
+    /// Matches one segment, only set on nodes whose path ends with `/`
+    param: Option<Box<Node<T>>>,
+
+    /// Matches the rest of the path, starting with `/`, or nothing
+    catch_all: Option<Leaf<T>>,
+
+    leaf: Option<Leaf<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(prefix: String) -> Self {
+        Self {
+            prefix,
+            children: Vec::new(),
+            param: None,
+            catch_all: None,
+            leaf: None,
+        }
+    }
+
+    /// The node reached by matching `path` from this node, splitting nodes and inserting
+    /// children as needed
+    fn static_node(&mut self, path: &str) -> &mut Node<T> {
+        if path.is_empty() {
+            return self;
+        }
+
+        let Some(i) = self
+            .children
+            .iter()
+            .position(|child| child.prefix.chars().next() == path.chars().next())
+        else {
+            self.children.push(Node::new(path.to_string()));
+
+            return self.children.last_mut().expect("Child was just inserted");
+        };
+
+        let child = &mut self.children[i];
+        let mut common = child
+            .prefix
+            .bytes()
+            .zip(path.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // Different characters may share leading bytes, only split between characters. The
+        // first characters are equal, so `common` stays above zero.
+        while !child.prefix.is_char_boundary(common) {
+            common -= 1;
+        }
+
+        if common < child.prefix.len() {
+            let suffix = child.prefix.split_off(common);
+            let mut split = Node::new(suffix);
+
+            std::mem::swap(&mut split.children, &mut child.children);
+            split.param = child.param.take();
+            split.catch_all = child.catch_all.take();
+            split.leaf = child.leaf.take();
+
+            child.children.push(split);
+        }
+
+        child.static_node(&path[common..])
+    }
+
+    /// Match `path` against the children of this node, after its prefix was matched
+    fn find<'t, 'p>(&'t self, path: &'p str, values: &mut Vec<&'p str>) -> Option<&'t Leaf<T>> {
+        if path.is_empty() {
+            if let Some(leaf) = &self.leaf {
+                return Some(leaf);
+            }
+        }
+
+        let child = self
+            .children
+            .iter()
+            .find(|child| path.starts_with(child.prefix.as_str()));
+
+        if let Some(leaf) = child.and_then(|child| child.find(&path[child.prefix.len()..], values))
+        {
+            return Some(leaf);
+        }
+
+        if let Some(param) = &self.param {
+            let end = path.find('/').unwrap_or(path.len());
+
+            values.push(&path[..end]);
+            if let Some(leaf) = param.find(&path[end..], values) {
+                return Some(leaf);
+            }
+            values.pop();
+        }
+
+        if let Some(leaf) = &self.catch_all {
+            let rest = match path {
+                "" => Some(""),
+                path => path.strip_prefix('/'),
+            };
+
+            if let Some(rest) = rest {
+                values.push(rest);
+
+                return Some(leaf);
+            }
+        }
+
+        None
+    }
+
+    fn collect_leaves<'t>(&'t self, leaves: &mut Vec<&'t Leaf<T>>) {
+        leaves.extend(self.leaf.iter());
+        leaves.extend(self.catch_all.iter());
+
+        for child in self.children.iter() {
+            child.collect_leaves(leaves);
+        }
+
+        if let Some(param) = &self.param {
+            param.collect_leaves(leaves);
+        }
+    }
+}
+
+/// Displays `segments` as a pattern accepted by `RadixTree::insert`
+pub(crate) fn render(segments: &[Segment]) -> String {
+    if segments.is_empty() {
+        return "/".to_string();
+    }
+
+    segments.iter().fold(String::new(), |pattern, segment| {
+        pattern
+            + &match segment {
+                Segment::Static(segment) => format!("/{}", segment),
+                Segment::Param(name) => format!("/:{}", name),
+                Segment::CatchAll(name) => format!("/*{}", name),
+            }
+    })
+}
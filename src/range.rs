@@ -0,0 +1,374 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+use tiny_http::{Method, StatusCode};
+
+use crate::{
+    conditional::{self, EntityTag},
+    request::Request,
+    response::{Body, Response},
+    service::Command,
+};
+
+/// Requests with more ranges are answered with the full body, serving many small ranges costs
+/// far more than the body itself.
+const MAX_RANGES: usize = 32;
+
+/// Answer `Range` requests for `response`. Only responses with status 200 and a file or in-memory
+/// body support ranges, they are marked with `Accept-Ranges: bytes`.
+///
+/// A single satisfiable range is sent as `StatusCode(206)` with `Content-Range`, multiple ranges
+/// as a `multipart/byteranges` body. Requests without any satisfiable range are answered with
+/// `StatusCode(416)`. Invalid `Range` headers are ignored and the full body is sent, as are
+/// ranges of a response no longer matching the `If-Range` header.
+///
+/// ```rust
+/// # use simple_http::{range::ranges, request::Request, response::Response, service::{Command, Service, System}, testing::TestClient, StatusCode};
+/// fn digits(_req: &mut Request, _data: &()) -> Command<()> {
+///     Command::respond(Response::bytes("0123456789"))
+/// }
+///
+/// let client = TestClient::new(
+///     Service::with_system("root", System::single(ranges).insert(digits)),
+///     (),
+/// );
+///
+/// let range = |header: &str| {
+///     let mut response = client.get("/").header("Range", header).send().unwrap();
+///     let content_range = response.get_header("Content-Range").map(|h| h.to_string());
+///
+///     (response.status_code().0, content_range, response.body_to_string().unwrap())
+/// };
+///
+/// assert_eq!(range("bytes=2-4"), (206, Some("bytes 2-4/10".into()), "234".into()));
+/// assert_eq!(range("bytes=-3"), (206, Some("bytes 7-9/10".into()), "789".into()));
+/// assert_eq!(range("bytes=8-20"), (206, Some("bytes 8-9/10".into()), "89".into()));
+///
+/// // Overlapping ranges are merged
+/// assert_eq!(range("bytes=0-2, 1-4"), (206, Some("bytes 0-4/10".into()), "01234".into()));
+///
+/// // Malformed headers and other units are ignored
+/// assert_eq!(range("bytes=5-3"), (200, None, "0123456789".into()));
+/// assert_eq!(range("bytes=a-b"), (200, None, "0123456789".into()));
+/// assert_eq!(range("items=0-1"), (200, None, "0123456789".into()));
+///
+/// // Nothing satisfiable
+/// assert_eq!(range("bytes=10-20"), (416, Some("bytes */10".into()), "".into()));
+///
+/// let mut response = client.get("/").header("Range", "bytes=0-0, 9-9").send().unwrap();
+/// assert_eq!(response.status_code(), StatusCode(206));
+/// assert!(response.get_header("Content-Type").unwrap().starts_with("multipart/byteranges"));
+/// let body = response.body_to_string().unwrap();
+/// assert!(body.contains("Content-Range: bytes 0-0/10\r\n\r\n0\r\n"));
+/// assert!(body.contains("Content-Range: bytes 9-9/10\r\n\r\n9\r\n"));
+/// ```
+pub fn apply(req: &Request, response: Response) -> Response {
+    if response.status_code().0 != 200
+        || !matches!(
+            response.body(),
+            Body::File(_) | Body::Bytes(_) | Body::Static(_)
+        )
+    {
+        return response;
+    }
+
+    let Some(length) = response.body_length().map(|length| length as u64) else {
+        return response;
+    };
+
+    let response = response
+        .without_header("Accept-Ranges")
+        .with_valid_header("Accept-Ranges", "bytes");
+
+    if *req.method() != Method::Get {
+        return response;
+    }
+
+    if !if_range_matches(req, &response) {
+        return response;
+    }
+
+    let Some(ranges) = req
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Range"))
+        .and_then(|h| parse(h.value.as_str(), length))
+    else {
+        return response;
+    };
+
+    match ranges.as_slice() {
+        [] => Response::empty(StatusCode(416))
+            .with_valid_header("Content-Range", &format!("bytes */{}", length)),
+        [range] => {
+            let content_range = content_range(range, length);
+            let range = range.clone();
+
+            response
+                .with_status(206)
+                .with_valid_header("Content-Range", &content_range)
+                .map_body(|body| slice(body, range))
+        }
+        _ => multipart(response, ranges, length),
+    }
+}
+
+/// System answering `Range` requests for the response produced by later systems, see `apply`
+pub fn ranges<Data>(req: &mut Request, _data: &Data) -> Command<Data> {
+    req.on_response(|req, response| apply(req, response));
+
+    Command::None
+}
+
+/// Ranges are only served if the validator in the `If-Range` header still matches the response,
+/// otherwise the client receives the full body to replace its outdated partial copy.
+fn if_range_matches(req: &Request, response: &Response) -> bool {
+    let Some(if_range) = req
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("If-Range"))
+        .map(|h| h.value.as_str())
+    else {
+        return true;
+    };
+
+    let (etag, last_modified) = conditional::validators(response);
+
+    match EntityTag::parse(if_range) {
+        Some(tag) => etag.is_some_and(|etag| etag.strong_eq(&tag)),
+        None => match (httpdate::parse_http_date(if_range), last_modified) {
+            (Ok(date), Some(last_modified)) => conditional::truncate(last_modified) == date,
+            _ => false,
+        },
+    }
+}
+
+/// Parses a `Range` header into the ranges satisfiable by a body of `length` bytes. `None` if
+/// the header is invalid or uses a unit other than bytes, overlapping ranges are merged.
+fn parse(header: &str, length: u64) -> Option<Vec<Range<u64>>> {
+    let (unit, specs) = header.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+
+    for spec in specs.split(',').map(|spec| spec.trim()) {
+        if spec.is_empty() {
+            continue;
+        }
+
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+
+        let range = if start.is_empty() {
+            // Suffix range, the last `end` bytes
+            let suffix = end.parse::<u64>().ok()?;
+
+            length.saturating_sub(suffix)..length
+        } else {
+            let start = start.parse::<u64>().ok()?;
+            let end = match end {
+                "" => length,
+                end => {
+                    let end = end.parse::<u64>().ok()?;
+                    if end < start {
+                        return None;
+                    }
+
+                    end.saturating_add(1).min(length)
+                }
+            };
+
+            start..end
+        };
+
+        if range.start < range.end {
+            ranges.push(range);
+        }
+    }
+
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+
+    // Ranges are sent in the requested order unless they overlap
+    let overlapping = ranges.iter().enumerate().any(|(i, a)| {
+        ranges[i + 1..]
+            .iter()
+            .any(|b| a.start < b.end && b.start < a.end)
+    });
+
+    if overlapping {
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<u64>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        ranges = merged;
+    }
+
+    Some(ranges)
+}
+
+fn content_range(range: &Range<u64>, length: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, length)
+}
+
+fn slice(body: Body, range: Range<u64>) -> Body {
+    match body {
+        Body::Bytes(bytes) => Body::Bytes(bytes[range.start as usize..range.end as usize].to_vec()),
+        Body::Static(bytes) => Body::Static(&bytes[range.start as usize..range.end as usize]),
+        Body::File(file) => {
+            let length = (range.end - range.start) as usize;
+            let reader = RangesReader::new(file, VecDeque::from([Part::Range(range)]));
+
+            Body::Reader(Box::new(reader), Some(length))
+        }
+        body => body,
+    }
+}
+
+/// Replaces the body of `response` with a `multipart/byteranges` body of `ranges`
+fn multipart(response: Response, ranges: Vec<Range<u64>>, length: u64) -> Response {
+    let mut random = [0; 12];
+    if getrandom::getrandom(&mut random).is_err() {
+        return response;
+    }
+
+    let boundary = random
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    let content_type = response
+        .get_header("Content-Type")
+        .map(|content_type| format!("Content-Type: {}\r\n", content_type))
+        .unwrap_or_default();
+
+    let mut parts = VecDeque::new();
+    for range in ranges {
+        let head = format!(
+            "\r\n--{}\r\n{}Content-Range: {}\r\n\r\n",
+            boundary,
+            content_type,
+            content_range(&range, length)
+        );
+
+        parts.push_back(Part::Bytes(head.into_bytes()));
+        parts.push_back(Part::Range(range));
+    }
+
+    parts.push_back(Part::Bytes(
+        format!("\r\n--{}--\r\n", boundary).into_bytes(),
+    ));
+
+    let body_length = parts.iter().map(|part| part.len()).sum::<u64>() as usize;
+
+    response
+        .with_status(206)
+        .with_valid_header(
+            "Content-Type",
+            &format!("multipart/byteranges; boundary={}", boundary),
+        )
+        .map_body(|body| match body {
+            Body::Bytes(bytes) => {
+                let reader = RangesReader::new(Cursor::new(bytes), parts);
+
+                Body::Reader(Box::new(reader), Some(body_length))
+            }
+            Body::Static(bytes) => {
+                let reader = RangesReader::new(Cursor::new(bytes), parts);
+
+                Body::Reader(Box::new(reader), Some(body_length))
+            }
+            Body::File(file) => {
+                let reader = RangesReader::new(file, parts);
+
+                Body::Reader(Box::new(reader), Some(body_length))
+            }
+            body => body,
+        })
+}
+
+enum Part {
+    Bytes(Vec<u8>),
+    Range(Range<u64>),
+}
+
+impl Part {
+    fn len(&self) -> u64 {
+        match self {
+            Part::Bytes(bytes) => bytes.len() as u64,
+            Part::Range(range) => range.end - range.start,
+        }
+    }
+}
+
+/// Reads ranges of a seekable source, interleaved with in-memory parts
+struct RangesReader<R> {
+    source: R,
+    parts: VecDeque<Part>,
+    offset: u64,
+}
+
+impl<R: Read + Seek> RangesReader<R> {
+    fn new(source: R, parts: VecDeque<Part>) -> Self {
+        Self {
+            source,
+            parts,
+            offset: 0,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for RangesReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(part) = self.parts.front() {
+            let remaining = part.len() - self.offset;
+
+            if remaining > 0 && !buf.is_empty() {
+                let amount = remaining.min(buf.len() as u64) as usize;
+
+                let read = match part {
+                    Part::Bytes(bytes) => {
+                        let start = self.offset as usize;
+                        buf[..amount].copy_from_slice(&bytes[start..start + amount]);
+
+                        amount
+                    }
+                    Part::Range(range) => {
+                        self.source
+                            .seek(SeekFrom::Start(range.start + self.offset))?;
+
+                        match self.source.read(&mut buf[..amount])? {
+                            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                            read => read,
+                        }
+                    }
+                };
+
+                self.offset += read as u64;
+
+                return Ok(read);
+            }
+
+            if remaining > 0 {
+                return Ok(0);
+            }
+
+            self.parts.pop_front();
+            self.offset = 0;
+        }
+
+        Ok(0)
+    }
+}
@@ -1,41 +1,125 @@
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    sync::Arc,
+};
 
-use tiny_http::Header;
+use tiny_http::{Header, Method};
 
-/// Wrapping request type, this should only be constructed from a tiny_http::Request internally.
-/// This is passed to all systems in an application.
+use crate::{
+    compression::{BodyDecoder, Encoding},
+    conditional,
+    cookie::parse_cookie_header,
+    cookie_jar::CookieJar,
+    response::Response,
+    session::Session,
+    urls::{UrlBuilder, UrlTable},
+};
 
+/// Callback run on the response produced for a request, see `Request::on_response`
+pub type ResponseHook = Box<dyn FnOnce(&mut Request, Response) -> Response>;
+
+/// Wrapping request type, constructed from a tiny_http::Request internally or by the
+/// `testing::TestClient`. This is passed to all systems in an application.
 pub struct Request<'a> {
+    method: Method,
+    url: String,
+    headers: Vec<Header>,
     url_values: HashMap<String, Vec<String>>,
+    urls: Arc<UrlTable>,
+    cookies: HashMap<String, String>,
+    session: Option<Session>,
+    response_hooks: Vec<ResponseHook>,
+
+    body: RequestBody<'a>,
+}
 
-    inner: &'a mut tiny_http::Request,
+/// Reads the body of the request, decoding it once a decoder is set
+struct RequestBody<'a> {
+    reader: Box<dyn Read + 'a>,
+    length: Option<usize>,
+    decoded: bool,
+}
+
+impl Read for RequestBody<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
 }
 
 impl<'a> Request<'a> {
-    pub(crate) fn from_request(
-        request: &'a mut tiny_http::Request,
+    pub(crate) fn new(
+        method: Method,
+        url: String,
+        headers: Vec<Header>,
+        body: &'a mut dyn Read,
+        body_length: Option<usize>,
         url_values: HashMap<String, Vec<String>>,
+        urls: Arc<UrlTable>,
     ) -> Self {
+        let mut cookies = HashMap::new();
+
+        for header in headers.iter().filter(|h| h.field.equiv("Cookie")) {
+            parse_cookie_header(header.value.as_str(), &mut cookies);
+        }
+
         Self {
+            method,
+            url,
+            headers,
             url_values,
+            urls,
+            cookies,
+            session: None,
+            response_hooks: Vec::new(),
 
-            inner: request,
+            body: RequestBody {
+                reader: Box::new(body),
+                length: body_length,
+                decoded: false,
+            },
         }
     }
 
+    pub(crate) fn from_request(
+        request: &'a mut tiny_http::Request,
+        url_values: HashMap<String, Vec<String>>,
+        urls: Arc<UrlTable>,
+    ) -> Self {
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let headers = request.headers().to_vec();
+        let body_length = request.body_length();
+
+        Self::new(
+            method,
+            url,
+            headers,
+            request.as_reader(),
+            body_length,
+            url_values,
+            urls,
+        )
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
     pub fn url(&self) -> &str {
-        self.inner.url()
+        &self.url
     }
 
     pub fn headers(&self) -> &[Header] {
-        self.inner.headers()
+        &self.headers
     }
 
+    /// Reader of the request body, decoded if `Decompression` was applied
     pub fn as_reader(&mut self) -> &mut dyn Read {
-        self.inner.as_reader()
+        &mut self.body
     }
 
-    pub fn body_to_string(&mut self) -> std::io::Result<String> {
+    pub fn body_to_string(&mut self) -> io::Result<String> {
         let mut body_buf = String::new();
 
         self.as_reader().read_to_string(&mut body_buf)?;
@@ -43,12 +127,122 @@ impl<'a> Request<'a> {
         Ok(body_buf)
     }
 
+    /// Id of the last Server-Sent Event received by a reconnecting client
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.headers()
+            .iter()
+            .find(|h| h.field.equiv("Last-Event-ID"))
+            .map(|h| h.value.as_str())
+    }
+
+    /// Length of the body as sent by the client, `None` if unknown or once the body is decoded
     pub fn body_length(&self) -> Option<usize> {
-        self.inner.body_length()
+        if self.body.decoded {
+            return None;
+        }
+
+        self.body.length
     }
 
     /// Get a url value from the inner map. See the `param` field at `Service#param`
+    ///
+    /// Values hold one percent-decoded segment each, so a value may contain a `/` sent as `%2F`
+    /// or a literal `%` sent as `%25`. Check values before using them as file paths.
     pub fn get_url_value(&self, field: &str) -> Option<&Vec<String>> {
         self.url_values.get(field)
     }
+
+    /// Start building the url of a service named with `Service::insert_name`
+    ///
+    /// ```rust
+    /// # use simple_http::{request::Request, service::{Command, Service, System, SystemError}, testing::TestClient};
+    /// fn create(req: &mut Request, _data: &()) -> Result<Command<()>, SystemError> {
+    ///     let location = req.url_for("user").value("id", "42").build()?;
+    ///
+    ///     Ok(Command::respond(location))
+    /// }
+    ///
+    /// let root = Service::root().fold(|s| {
+    ///     s.insert_child(Service::with_system("users", System::fallible(create)).fold(|s| {
+    ///         s.insert_child(Service::with_path(":id").insert_name("user"));
+    ///     }));
+    /// });
+    ///
+    /// let mut response = TestClient::new(root, ()).post("/users").send().unwrap();
+    /// assert_eq!(response.body_to_string().unwrap(), "/users/42");
+    /// ```
+    pub fn url_for(&self, name: &str) -> UrlBuilder<'_> {
+        self.urls.url_for(name)
+    }
+
+    /// Urls of the named services of the tree handling the request
+    pub fn urls(&self) -> &UrlTable {
+        &self.urls
+    }
+
+    /// Get a cookie sent by the client in the `Cookie` header
+    pub fn get_cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(|value| value.as_str())
+    }
+
+    /// All cookies sent by the client in the `Cookie` header
+    pub fn cookies(&self) -> &HashMap<String, String> {
+        &self.cookies
+    }
+
+    /// Get a cookie issued with `CookieJar::sign`, `None` if missing or tampered with
+    pub fn get_signed_cookie(&self, jar: &CookieJar, name: &str) -> Option<String> {
+        jar.verify(name, self.get_cookie(name)?)
+    }
+
+    /// Get a cookie issued with `CookieJar::encrypt`, `None` if missing or tampered with
+    pub fn get_private_cookie(&self, jar: &CookieJar, name: &str) -> Option<String> {
+        jar.decrypt(name, self.get_cookie(name)?)
+    }
+
+    /// Get the session loaded by `session::load`
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// Get the session loaded by `session::load` for modification. Changes are persisted once a
+    /// response is produced.
+    pub fn session_mut(&mut self) -> Option<&mut Session> {
+        self.session.as_mut()
+    }
+
+    pub(crate) fn set_session(&mut self, session: Session) {
+        self.session = Some(session);
+    }
+
+    pub(crate) fn take_session(&mut self) -> Option<Session> {
+        self.session.take()
+    }
+
+    /// Decode the rest of the body with `encoding`, failing once more than `max_size` bytes
+    /// are decoded
+    pub(crate) fn set_decoder(&mut self, encoding: Encoding, max_size: usize) {
+        let reader = std::mem::replace(&mut self.body.reader, Box::new(io::empty()));
+
+        self.body.reader = Box::new(BodyDecoder::new(encoding, max_size, reader));
+        self.body.decoded = true;
+    }
+
+    /// Register a callback run on the response once a system responds to this request. Hooks
+    /// run in reverse order of registration, like unwinding middleware.
+    pub fn on_response(&mut self, hook: impl FnOnce(&mut Request, Response) -> Response + 'static) {
+        self.response_hooks.push(Box::new(hook));
+    }
+
+    /// Evaluate the preconditions of the request, see `conditional::apply`, then run all hooks
+    /// registered with `on_response`
+    pub(crate) fn finish(&mut self, response: Response) -> Response {
+        let mut response = conditional::apply(self, response);
+
+        while let Some(hook) = self.response_hooks.pop() {
+            response = hook(self, response);
+        }
+
+        response
+    }
 }
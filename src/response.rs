@@ -1,18 +1,99 @@
-use std::{fs::File, io::Read, sync::mpsc::Receiver};
+use std::{
+    fs::File,
+    io::{self, Cursor, Read},
+    path::Path,
+    sync::mpsc::Receiver,
+    time::SystemTime,
+};
 
-use tiny_http::{Header, StatusCode};
+use tiny_http::{Header, Method, StatusCode};
+
+use crate::{
+    conditional::EntityTag,
+    cookie::Cookie,
+    error::HeaderError,
+    mime,
+    stream::{write_chunked, ChannelReader, Chunk, StreamWriter},
+};
+
+/// Data sent after the headers of a `Response`
+pub(crate) enum Body {
+    Empty,
+    Bytes(Vec<u8>),
+    Static(&'static [u8]),
+    File(File),
+    Reader(Box<dyn Read + Send>, Option<usize>),
+    Stream(Receiver<Chunk>),
+}
+
+/// Kind of redirect produced by `Response::redirect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redirect {
+    /// 301, clients may change the method to GET
+    MovedPermanently,
+
+    /// 302, clients may change the method to GET
+    Found,
+
+    /// 303, clients always follow with a GET request
+    SeeOther,
+
+    /// 307, clients must keep the method and body
+    Temporary,
+
+    /// 308, clients must keep the method and body
+    Permanent,
+}
+
+impl Redirect {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Redirect::MovedPermanently => StatusCode(301),
+            Redirect::Found => StatusCode(302),
+            Redirect::SeeOther => StatusCode(303),
+            Redirect::Temporary => StatusCode(307),
+            Redirect::Permanent => StatusCode(308),
+        }
+    }
+}
 
 /// Wrapping response body intended to be returned by services.
-pub struct Response(tiny_http::ResponseBox);
+pub struct Response {
+    status_code: StatusCode,
+    headers: Vec<Header>,
+    body: Body,
+    additional_headers: Option<Receiver<Header>>,
+}
 
 impl From<Response> for tiny_http::ResponseBox {
     fn from(value: Response) -> Self {
-        value.0
+        let (status_code, headers, reader, length) = value.into_parts();
+
+        tiny_http::Response::new(status_code, headers, reader, length, None).boxed()
     }
 }
 
 impl Response {
-    /// General response constructor
+    /// General response constructor. The headers sent on `additional_headers` by the time the
+    /// response is sent are added, the receiver is not waited on.
+    ///
+    /// ```rust
+    /// # use std::sync::mpsc;
+    /// # use simple_http::{request::Request, response::Response, service::{Command, Service, System}, testing::TestClient};
+    /// # use tiny_http::{Header, StatusCode};
+    /// fn page(_req: &mut Request, _data: &()) -> Command<()> {
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let response = Response::new(StatusCode(200), Vec::new(), "page".as_bytes(), Some(4), Some(receiver));
+    ///
+    ///     // Queued after the response was constructed
+    ///     sender.send(Header::from_bytes("X-Late", "yes").unwrap()).unwrap();
+    ///
+    ///     Command::respond(response)
+    /// }
+    ///
+    /// let client = TestClient::new(Service::with_system("root", System::single(page)), ());
+    /// assert_eq!(client.get("/").send().unwrap().get_header("X-Late"), Some("yes"));
+    /// ```
     pub fn new<R: Read + Send + 'static>(
         status_code: StatusCode,
         headers: Vec<Header>,
@@ -20,27 +101,546 @@ impl Response {
         data_length: Option<usize>,
         additional_headers: Option<Receiver<Header>>,
     ) -> Self {
-        Self(
-            tiny_http::Response::new(status_code, headers, data, data_length, additional_headers)
-                .boxed(),
-        )
+        Self {
+            status_code,
+            headers,
+            body: Body::Reader(Box::new(data), data_length),
+            additional_headers,
+        }
     }
 
-    /// Status code 200 with file in the body
+    /// Builder for responses with multiple headers, see `ResponseBuilder`
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// Status code 200 with file in the body. A weak `ETag` and `Last-Modified` are derived from
+    /// the file metadata, see `conditional::apply`. No `Content-Type` is set, see `open`.
     pub fn file(file: File) -> Self {
-        Self(tiny_http::Response::from_file(file).boxed())
+        let modified = file
+            .metadata()
+            .and_then(|metadata| Ok((metadata.len(), metadata.modified()?)));
+
+        let response = Self {
+            status_code: StatusCode(200),
+            headers: Vec::new(),
+            body: Body::File(file),
+            additional_headers: None,
+        };
+
+        match modified {
+            Ok((length, modified)) => response
+                .with_etag(&EntityTag::from_metadata(length, modified))
+                .with_last_modified(modified),
+            Err(_) => response,
+        }
+    }
+
+    /// Opens the file at `path` as a `Response::file`, with the `Content-Type` of its extension
+    /// in the built-in `MimeTypes`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        Ok(mime::built_in().apply(path, Self::file(File::open(path)?)))
     }
 
     /// Empty response used to send status codes
-    pub fn empty(status_code: StatusCode) -> Self {
-        Self(tiny_http::Response::empty(status_code).boxed())
+    pub fn empty(status_code: impl Into<StatusCode>) -> Self {
+        Self {
+            status_code: status_code.into(),
+            headers: Vec::new(),
+            body: Body::Empty,
+            additional_headers: None,
+        }
+    }
+
+    /// Status code 200 with `text/plain` utf-8 body
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::bytes(text.into().into_bytes())
+            .with_valid_header("Content-Type", "text/plain; charset=utf-8")
+    }
+
+    /// Status code 200 with `text/html` utf-8 body
+    pub fn html(html: impl Into<String>) -> Self {
+        Self::bytes(html.into().into_bytes())
+            .with_valid_header("Content-Type", "text/html; charset=utf-8")
+    }
+
+    /// Status code 200 with `application/octet-stream` body
+    pub fn bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status_code: StatusCode(200),
+            headers: Vec::new(),
+            body: Body::Bytes(bytes.into()),
+            additional_headers: None,
+        }
+        .with_valid_header("Content-Type", "application/octet-stream")
+    }
+
+    /// Status code 200 with a body borrowed from the binary, avoiding a copy per response
+    pub(crate) fn from_static(bytes: &'static [u8]) -> Self {
+        Self {
+            status_code: StatusCode(200),
+            headers: Vec::new(),
+            body: Body::Static(bytes),
+            additional_headers: None,
+        }
+    }
+
+    /// Status code 200 with a body written incrementally by `producer` on its own thread. The body
+    /// is sent with chunked transfer encoding, every flush of the writer sends the data written
+    /// so far to the client.
+    ///
+    /// If `producer` fails, the error is logged and the response is aborted, see
+    /// `StreamWriter::abort`.
+    ///
+    /// ```rust
+    /// # use std::io::{self, Write};
+    /// # use simple_http::{request::Request, response::Response, service::{Command, Service, System}, testing::TestClient};
+    /// let response = Response::stream(|writer| {
+    ///     for row in 0..1000 {
+    ///         writeln!(writer, "{},{}", row, row * row)?;
+    ///     }
+    ///
+    ///     Ok(())
+    /// });
+    ///
+    /// fn export(_req: &mut Request, _data: &()) -> Command<()> {
+    ///     Command::respond(Response::stream(|writer| {
+    ///         writeln!(writer, "id,name")?;
+    ///
+    ///         Err(io::Error::other("Database went away"))
+    ///     }))
+    /// }
+    ///
+    /// // A failed export never reaches the client as a complete body
+    /// let client = TestClient::new(Service::with_system("root", System::single(export)), ());
+    /// assert!(client.get("/").send().unwrap().body_to_string().is_err());
+    /// ```
+    pub fn stream(
+        producer: impl FnOnce(&mut StreamWriter) -> io::Result<()> + Send + 'static,
+    ) -> Self {
+        let (mut writer, response) = Self::channel();
+
+        std::thread::spawn(move || {
+            if let Err(e) = producer(&mut writer) {
+                log::error!("Aborting streaming response: {}", e);
+
+                writer.abort(e);
+            }
+        });
+
+        response
+    }
+
+    /// Status code 200 with a body written through the returned writer, which can be moved to
+    /// any thread. The response ends once the writer is dropped. See `Response::stream`.
+    pub fn channel() -> (StreamWriter, Self) {
+        let (writer, receiver) = StreamWriter::new();
+
+        let response = Self {
+            status_code: StatusCode(200),
+            headers: Vec::new(),
+            body: Body::Stream(receiver),
+            additional_headers: None,
+        };
+
+        (writer, response)
+    }
+
+    /// Redirect to `location`. Characters not allowed in a header are percent-encoded.
+    pub fn redirect(redirect: Redirect, location: &str) -> Self {
+        let mut encoded = String::with_capacity(location.len());
+        for byte in location.bytes() {
+            if byte.is_ascii_graphic() {
+                encoded.push(byte as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+
+        Self::empty(redirect.status_code()).with_valid_header("Location", &encoded)
+    }
+
+    /// Replace the status code
+    pub fn with_status(mut self, status_code: impl Into<StatusCode>) -> Self {
+        self.status_code = status_code.into();
+
+        self
+    }
+
+    /// Insert a header. `Content-Type` replaces any previous value, other headers are appended.
+    pub fn with_header(self, key: &str, value: &str) -> Result<Self, HeaderError> {
+        Ok(self.with_typed_header(make_header(key, value)?))
+    }
+
+    /// Insert an already constructed header, see `with_header`
+    pub fn with_typed_header(mut self, header: Header) -> Self {
+        if header.field.equiv("Content-Type") {
+            self.headers.retain(|h| !h.field.equiv("Content-Type"));
+        }
+
+        self.headers.push(header);
+
+        self
+    }
+
+    /// Remove all headers named `key`
+    pub fn without_header(mut self, key: &str) -> Self {
+        self.headers
+            .retain(|h| !h.field.as_str().as_str().eq_ignore_ascii_case(key));
+
+        self
+    }
+
+    /// Replace the `ETag` header, see `conditional::apply`
+    pub fn with_etag(self, etag: &EntityTag) -> Self {
+        self.without_header("ETag")
+            .with_valid_header("ETag", &etag.to_string())
+    }
+
+    /// Replace the `Last-Modified` header, see `conditional::apply`
+    pub fn with_last_modified(self, modified: SystemTime) -> Self {
+        self.without_header("Last-Modified")
+            .with_valid_header("Last-Modified", &httpdate::fmt_http_date(modified))
+    }
+
+    /// Insert a `Set-Cookie` header. Use `Cookie::removal` to remove a cookie from the client.
+    pub fn with_cookie(self, cookie: Cookie) -> Self {
+        self.with_valid_header("Set-Cookie", &cookie.to_string())
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    pub fn headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    /// Get the value of the first header named `key`
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(key))
+            .map(|h| h.value.as_str())
+    }
+
+    /// Length of the body if known before sending
+    pub(crate) fn body_length(&self) -> Option<usize> {
+        match &self.body {
+            Body::Empty => Some(0),
+            Body::Bytes(bytes) => Some(bytes.len()),
+            Body::Static(bytes) => Some(bytes.len()),
+            Body::File(file) => file.metadata().ok().map(|m| m.len() as usize),
+            Body::Reader(_, length) => *length,
+            Body::Stream(_) => None,
+        }
+    }
+
+    pub(crate) fn body(&self) -> &Body {
+        &self.body
+    }
+
+    /// Replace the body, used by layers transforming the response
+    pub(crate) fn map_body(mut self, f: impl FnOnce(Body) -> Body) -> Self {
+        self.body = f(self.body);
+
+        self
+    }
+
+    /// Respond to `request`. Streaming bodies are sent with chunked transfer encoding and flushed
+    /// as they are produced, unless the client doesn't support it.
+    pub(crate) fn send(mut self, request: tiny_http::Request) {
+        self.drain_additional_headers();
+
+        let chunked = *request.http_version() >= (1, 1) && *request.method() != Method::Head;
+
+        match self.body {
+            Body::Stream(receiver) if chunked => {
+                let _ = write_chunked(
+                    request.into_writer(),
+                    self.status_code,
+                    &self.headers,
+                    receiver,
+                );
+            }
+            _ => {
+                let _ = request.respond(tiny_http::ResponseBox::from(self));
+            }
+        }
+    }
+
+    /// Status code, headers, body reader and body length if known
+    pub(crate) fn into_parts(
+        mut self,
+    ) -> (StatusCode, Vec<Header>, Box<dyn Read + Send>, Option<usize>) {
+        self.drain_additional_headers();
+
+        let (reader, length): (Box<dyn Read + Send>, _) = match self.body {
+            Body::Empty => (Box::new(std::io::empty()), Some(0)),
+            Body::Bytes(bytes) => {
+                let length = bytes.len();
+                (Box::new(Cursor::new(bytes)), Some(length))
+            }
+            Body::Static(bytes) => (Box::new(bytes), Some(bytes.len())),
+            Body::File(file) => {
+                let length = file.metadata().ok().map(|m| m.len() as usize);
+                (Box::new(file), length)
+            }
+            Body::Reader(reader, length) => (reader, length),
+            Body::Stream(receiver) => (Box::new(ChannelReader::new(receiver)), None),
+        };
+
+        (self.status_code, self.headers, reader, length)
+    }
+
+    /// Adds the headers queued on the receiver given to `new` so far
+    fn drain_additional_headers(&mut self) {
+        if let Some(additional_headers) = self.additional_headers.take() {
+            self.headers.extend(additional_headers.try_iter());
+        }
+    }
+
+    /// Inserts a header known to be valid
+    pub(crate) fn with_valid_header(self, key: &str, value: &str) -> Self {
+        self.with_typed_header(make_header(key, value).expect("Header should be valid"))
+    }
+}
+
+/// Fluent response constructor, header errors are reported once the response is built.
+///
+/// ```rust
+/// # use simple_http::response::Response;
+/// let response = Response::builder()
+///     .status(201)
+///     .header("Location", "/items/1")
+///     .header("X-Request-Id", "abc")
+///     .text("Created");
+///
+/// assert!(response.is_ok());
+/// ```
+pub struct ResponseBuilder {
+    status_code: StatusCode,
+    headers: Vec<Header>,
+    error: Option<HeaderError>,
+}
+
+impl ResponseBuilder {
+    /// Constructs a builder for a response with status code 200
+    pub fn new() -> Self {
+        Self {
+            status_code: StatusCode(200),
+            headers: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn status(mut self, status_code: impl Into<StatusCode>) -> Self {
+        self.status_code = status_code.into();
+
+        self
+    }
+
+    /// Insert a header, an invalid header is reported when the response is built
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        match make_header(key, value) {
+            Ok(header) => self.headers.push(header),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+
+        self
+    }
+
+    pub fn cookie(mut self, cookie: Cookie) -> Self {
+        self.headers
+            .push(make_header("Set-Cookie", &cookie.to_string()).expect("Header should be valid"));
+
+        self
     }
 
-    /// Insert a header to the underlying `Response` object
-    pub fn with_header(mut self, key: &str, value: &str) -> Result<Self, ()> {
-        let header = Header::from_bytes(key, value)?;
-        self.0 = self.0.with_header(header);
+    /// Build a response with no body
+    pub fn empty(self) -> Result<Response, HeaderError> {
+        self.build(Response::empty(StatusCode(200)))
+    }
+
+    /// Build a response with a `text/plain` body unless another `Content-Type` was set
+    pub fn text(self, text: impl Into<String>) -> Result<Response, HeaderError> {
+        self.build(Response::text(text))
+    }
+
+    /// Build a response with a `text/html` body unless another `Content-Type` was set
+    pub fn html(self, html: impl Into<String>) -> Result<Response, HeaderError> {
+        self.build(Response::html(html))
+    }
+
+    /// Build a response with an `application/octet-stream` body unless another `Content-Type`
+    /// was set
+    pub fn bytes(self, bytes: impl Into<Vec<u8>>) -> Result<Response, HeaderError> {
+        self.build(Response::bytes(bytes))
+    }
+
+    /// Build a response reading the body from `reader`
+    pub fn reader<R: Read + Send + 'static>(
+        self,
+        reader: R,
+        length: Option<usize>,
+    ) -> Result<Response, HeaderError> {
+        self.build(Response::new(
+            StatusCode(200),
+            Vec::new(),
+            reader,
+            length,
+            None,
+        ))
+    }
+
+    fn build(self, response: Response) -> Result<Response, HeaderError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let response = self.headers.into_iter().fold(response, |response, header| {
+            response.with_typed_header(header)
+        });
+
+        Ok(response.with_status(self.status_code))
+    }
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Constructs a header, rejecting names that aren't tokens and values with control characters
+/// that would allow header injection.
+pub(crate) fn make_header(key: &str, value: &str) -> Result<Header, HeaderError> {
+    let valid_name = !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+
+    if !valid_name {
+        return Err(HeaderError::InvalidName(key.to_string()));
+    }
+
+    let valid_value = value
+        .bytes()
+        .all(|b| b == b'\t' || (0x20..0x7F).contains(&b));
+
+    let header = valid_value
+        .then(|| Header::from_bytes(key, value).ok())
+        .flatten();
+
+    header.ok_or_else(|| HeaderError::InvalidValue {
+        name: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Conversion of handler return values into a `Response`, see `Command::respond`.
+///
+/// Implementing `IntoResponse` for an error type allows handlers to use `?` on fallible
+/// operations and respond with the resulting `Result`.
+///
+/// ```rust
+/// # use simple_http::{response::IntoResponse, StatusCode};
+/// fn parse_id(value: &str) -> Result<String, (StatusCode, &'static str)> {
+///     let id = value
+///         .parse::<u32>()
+///         .map_err(|_| (StatusCode(400), "Invalid id"))?;
+///
+///     Ok(format!("Item {}", id))
+/// }
+///
+/// let response = parse_id("abc").into_response();
+///
+/// assert_eq!(response.status_code(), StatusCode(400));
+/// ```
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+/// Empty response with the status code
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response {
+        Response::empty(self)
+    }
+}
+
+/// Status code 200 with `text/plain` body
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+/// Status code 200 with `text/plain` body
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+/// Status code 200 with `application/octet-stream` body
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response {
+        Response::bytes(self)
+    }
+}
+
+/// Replaces the status code of the body
+impl<B: IntoResponse> IntoResponse for (StatusCode, B) {
+    fn into_response(self) -> Response {
+        self.1.into_response().with_status(self.0)
+    }
+}
+
+/// Replaces the status code of the body and inserts the headers
+impl<B: IntoResponse> IntoResponse for (StatusCode, Vec<Header>, B) {
+    fn into_response(self) -> Response {
+        self.1
+            .into_iter()
+            .fold(self.2.into_response(), |response, header| {
+                response.with_typed_header(header)
+            })
+            .with_status(self.0)
+    }
+}
+
+/// Replaces the status code of the body and inserts the headers. Invalid headers respond with
+/// `StatusCode(500)`.
+impl<B: IntoResponse, const N: usize> IntoResponse for (StatusCode, [(&str, &str); N], B) {
+    fn into_response(self) -> Response {
+        let response = self
+            .1
+            .into_iter()
+            .try_fold(self.2.into_response(), |response, (key, value)| {
+                response.with_header(key, value)
+            });
+
+        match response {
+            Ok(response) => response.with_status(self.0),
+            Err(_) => Response::empty(StatusCode(500)),
+        }
+    }
+}
 
-        Ok(self)
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(e) => e.into_response(),
+        }
     }
 }
@@ -0,0 +1,404 @@
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
+
+use crate::{
+    error::RouteError,
+    percent::{decode, encode, is_unreserved_byte},
+    radix::{render, RadixTree, Segment},
+    service::{Param, Service, System},
+    urls::UrlTable,
+};
+
+/// Matches urls against a `Service` tree. Used by `Application` and `TestClient`, and usable on
+/// its own to check which services handle a url.
+///
+/// The tree is compiled into a `RadixTree` of every path a service can be reached at, including
+/// the segments collected by its `Param`. Static paths take precedence over children whose path
+/// starts with `:`, those over children whose path starts with `*`, see `Service::insert_child`.
+///
+/// Only the path of a url is matched, its query string is ignored. Segments are compared after
+/// percent-encoding them the way `UrlBuilder` does, so `/a%20b` reaches a child at `a b` and
+/// `/a@b` one at `a@b` whichever characters the client encoded. A url running out of segments
+/// before a `CollectExact` parameter collected all of its segments still reaches the service,
+/// without a value for the parameter.
+///
+/// ```rust
+/// # use simple_http::{request::Request, router::Router, service::{Command, Param, Service, System}};
+/// fn user(_req: &mut Request, _data: &()) -> Command<()> {
+///     Command::None
+/// }
+///
+/// let root = Service::root().fold(|s| {
+///     s.insert_child(
+///         Service::with_system("users", System::single(user))
+///             .insert_param(Param::CollectExact("id".to_string(), 1)),
+///     );
+///     s.insert_child(Service::with_path("posts").fold(|s| {
+///         s.insert_child(Service::with_path("latest"));
+///         s.insert_child(Service::with_system(":slug", System::single(user)));
+///     }));
+/// });
+///
+/// let router = Router::new(root);
+///
+/// let route = router.route("/users/42?fields=name").unwrap();
+/// assert_eq!(route.pattern(), "/users/:id");
+/// assert_eq!(route.get_url_value("id"), Some(&vec!["42".to_string()]));
+/// assert_eq!(route.systems().count(), 1);
+///
+/// assert_eq!(router.route("/posts/latest").unwrap().pattern(), "/posts/latest");
+/// assert_eq!(router.route("/posts/hello?page=2").unwrap().pattern(), "/posts/:slug");
+///
+/// // Urls missing segments of a `CollectExact` parameter reach the service without a value
+/// let route = router.route("/users").unwrap();
+/// assert_eq!(route.pattern(), "/users");
+/// assert_eq!(route.get_url_value("id"), None);
+/// assert_eq!(route.systems().count(), 1);
+///
+/// assert!(router.route("/comments").is_none());
+/// ```
+pub struct Router<Data> {
+    root: Service<Data>,
+    tree: RadixTree<Endpoint>,
+    urls: Arc<UrlTable>,
+}
+
+/// Services run for a pattern of the compiled tree
+struct Endpoint {
+    /// Paths of the children leading from the root to the last service
+    path: Vec<String>,
+
+    /// Url values present even if no segment was collected
+    names: Vec<String>,
+
+    /// The last parameter is a catch-all, its value is split into segments
+    catch_all: bool,
+
+    /// Trailing segments of a `CollectExact` parameter that ran out of segments, dropped with
+    /// its value
+    partial: usize,
+}
+
+impl<Data> Router<Data> {
+    /// Compiles the routes of `root`. Problems reported by `try_new` are logged, routes
+    /// conflicting with a route compiled before, such as two children with paths starting with
+    /// `:`, are ignored.
+    pub fn new(root: Service<Data>) -> Self {
+        let (router, errors) = Self::compile(root);
+
+        for e in errors {
+            log::warn!("Ignoring route: {}", e);
+        }
+
+        router
+    }
+
+    /// Compiles the routes of `root` like `new`, failing if any route conflicts with another,
+    /// was replaced by a child inserted at the same path, or can never be reached. Meant to
+    /// catch mistakes in the tree at startup, `Application::new` uses it.
+    ///
+    /// ```rust
+    /// # use simple_http::{error::RouteError, router::Router, service::{Param, Service}};
+    /// let root: Service<()> = Service::root().fold(|s| {
+    ///     s.insert_child(Service::with_path("users").fold(|s| {
+    ///         s.insert_child(Service::with_path(":id"));
+    ///         s.insert_child(Service::with_path(":name"));
+    ///     }));
+    ///     s.insert_child(
+    ///         Service::with_path("files")
+    ///             .insert_param(Param::CollectAll("path".to_string()))
+    ///             .fold(|s| s.insert_child(Service::with_path("latest"))),
+    ///     );
+    ///     s.insert_child(Service::with_path("posts"));
+    ///     s.insert_child(Service::with_path("posts"));
+    /// });
+    ///
+    /// let errors = Router::try_new(root).err().unwrap();
+    /// assert_eq!(errors.len(), 3);
+    /// assert!(errors.contains(&RouteError::DuplicatePath("/posts".to_string())));
+    /// assert!(errors.contains(&RouteError::Conflict {
+    ///     pattern: "/users/:name".to_string(),
+    ///     existing: "/users/:id".to_string(),
+    /// }));
+    ///
+    /// for e in errors {
+    ///     println!("{}", e);
+    /// }
+    /// ```
+    pub fn try_new(root: Service<Data>) -> Result<Self, Vec<RouteError>> {
+        match Self::compile(root) {
+            (router, errors) if errors.is_empty() => Ok(router),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    fn compile(root: Service<Data>) -> (Self, Vec<RouteError>) {
+        let mut tree = RadixTree::new();
+        let mut errors = Vec::new();
+
+        compile(
+            &mut tree,
+            &mut errors,
+            &root,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+
+        let urls = Arc::new(UrlTable::collect(&root, &mut errors));
+
+        (Self { root, tree, urls }, errors)
+    }
+
+    pub fn root(&self) -> &Service<Data> {
+        &self.root
+    }
+
+    /// Urls of the named services of the tree, see `Service::insert_name`
+    pub fn urls(&self) -> &Arc<UrlTable> {
+        &self.urls
+    }
+
+    /// Finds the services reached by the path of `url`, ignoring its query string, and the url
+    /// values collected on the way, percent-decoded. `None` if no service matches the url.
+    pub fn route(&self, url: &str) -> Option<Route<'_, Data>> {
+        let path = url.split_once('?').map_or(url, |(path, _)| path);
+
+        // Anything before the first `/`, such as the `*` of `OPTIONS *`, is not part of the path
+        let path = path.find('/').map_or("", |start| &path[start..]);
+        let path = normalize(path);
+
+        let found = self.tree.at(&path)?;
+        let endpoint = found.value();
+
+        let mut services = vec![&self.root];
+        for path in &endpoint.path {
+            let parent = services[services.len() - 1];
+            services.push(parent.get_child(path)?);
+        }
+
+        let mut url_values = endpoint
+            .names
+            .iter()
+            .map(|name| (name.clone(), Vec::new()))
+            .collect::<HashMap<String, Vec<String>>>();
+
+        let params = found.params();
+        let params = &params[..params.len() - endpoint.partial];
+
+        let last = params.len().saturating_sub(1);
+        for (i, (name, value)) in params.iter().enumerate() {
+            let values = url_values.entry(name.to_string()).or_default();
+
+            if endpoint.catch_all && i == last {
+                if !value.is_empty() {
+                    values.extend(value.split('/').map(decode));
+                }
+            } else {
+                values.push(decode(value));
+            }
+        }
+
+        Some(Route {
+            pattern: found.pattern(),
+            services,
+            url_values,
+        })
+    }
+}
+
+impl<Data> From<Service<Data>> for Router<Data> {
+    fn from(value: Service<Data>) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Inserts the patterns `service` is reached at below `segments` into `tree`, then those of its
+/// children. Routes that could not be inserted are added to `errors`.
+fn compile<Data>(
+    tree: &mut RadixTree<Endpoint>,
+    errors: &mut Vec<RouteError>,
+    service: &Service<Data>,
+    mut segments: Vec<Segment>,
+    path: Vec<String>,
+    mut names: Vec<String>,
+) {
+    let mut insert = |segments: &[Segment], names: &[String], partial: usize| {
+        let endpoint = Endpoint {
+            path: path.clone(),
+            names: names.to_vec(),
+            catch_all: matches!(segments.last(), Some(Segment::CatchAll(_))),
+            partial,
+        };
+
+        if let Err(e) = tree.insert_segments(segments, endpoint) {
+            errors.push(e);
+        }
+    };
+
+    match service.param() {
+        Param::None => insert(&segments, &names, 0),
+        Param::CollectExact(name, amount) => {
+            // Urls running out of segments still reach the service, without the value
+            for collected in 0..*amount {
+                insert(&segments, &names, collected);
+                segments.push(Segment::Param(name.clone()));
+            }
+
+            names.push(name.clone());
+            insert(&segments, &names, 0);
+        }
+        Param::CollectMaybe(name, amount) => {
+            names.push(name.clone());
+
+            for _ in 0..*amount {
+                insert(&segments, &names, 0);
+                segments.push(Segment::Param(name.clone()));
+            }
+
+            insert(&segments, &names, 0);
+        }
+        Param::CollectAll(name) => {
+            names.push(name.clone());
+            segments.push(Segment::CatchAll(name.clone()));
+
+            insert(&segments, &names, 0);
+        }
+    }
+
+    // Conflicts resolve the same way on every start
+    let children = service.sorted_children();
+
+    // Pattern of a child as written, without parsing its path
+    let pattern = |path: &str| {
+        let mut segments = segments.clone();
+        segments.push(Segment::Static(path.to_string()));
+
+        render(&segments)
+    };
+
+    let mut replaced = service.replaced().to_vec();
+    replaced.sort();
+    replaced.dedup();
+
+    errors.extend(
+        replaced
+            .iter()
+            .map(|path| RouteError::DuplicatePath(pattern(path))),
+    );
+
+    // Nothing can follow a catch-all
+    if let Some(Segment::CatchAll(_)) = segments.last() {
+        errors.extend(children.iter().map(|child| RouteError::Unreachable {
+            pattern: pattern(child.path()),
+            reason: match service.param() {
+                Param::CollectAll(_) => "its parent collects all remaining segments",
+                _ => "it follows a catch-all",
+            },
+        }));
+
+        return;
+    }
+
+    for child in children {
+        // Segments never contain `/`, such children are never reached
+        if child.path().contains('/') {
+            errors.push(RouteError::Unreachable {
+                pattern: pattern(child.path()),
+                reason: "url segments can not contain '/'",
+            });
+
+            continue;
+        }
+
+        let mut segments = segments.clone();
+        segments.push(child_segment(child.path()));
+
+        let mut path = path.clone();
+        path.push(child.path().to_string());
+
+        compile(tree, errors, child, segments, path, names.clone());
+    }
+}
+
+/// Segment of the pattern of a child at `path`, static paths are percent-encoded like the
+/// segments of the urls matched against them
+pub(crate) fn child_segment(path: &str) -> Segment {
+    match Segment::parse(path) {
+        Segment::Static(path) => Segment::Static(encode(&path, is_unreserved_byte)),
+        segment => segment,
+    }
+}
+
+/// Percent-encodes every segment of `path` the way `UrlBuilder` does, decoding it first so
+/// escapes chosen by the client don't matter
+fn normalize(path: &str) -> Cow<'_, str> {
+    if path
+        .bytes()
+        .all(|byte| byte == b'/' || is_unreserved_byte(byte))
+    {
+        return Cow::Borrowed(path);
+    }
+
+    let segments = path
+        .split('/')
+        .map(|segment| encode(&decode(segment), is_unreserved_byte))
+        .collect::<Vec<_>>();
+
+    Cow::Owned(segments.join("/"))
+}
+
+/// Services matched by a url and the url values they collected, see `Router::route`
+pub struct Route<'r, Data> {
+    pattern: &'r str,
+    services: Vec<&'r Service<Data>>,
+    url_values: HashMap<String, Vec<String>>,
+}
+
+impl<'r, Data> Route<'r, Data> {
+    /// The pattern of the matched route, e.g. `/users/:id` for a `users` service collecting one
+    /// segment named `id`
+    pub fn pattern(&self) -> &'r str {
+        self.pattern
+    }
+
+    /// Services passed from the root to the last matched service
+    pub fn services(&self) -> impl Iterator<Item = &'r Service<Data>> + '_ {
+        self.services.iter().copied()
+    }
+
+    /// Systems of the matched services, in the order they run
+    pub fn systems(&self) -> impl Iterator<Item = &'r System<Data>> + '_ {
+        self.services()
+            .filter_map(|service| service.systems().as_ref())
+    }
+
+    pub fn url_values(&self) -> &HashMap<String, Vec<String>> {
+        &self.url_values
+    }
+
+    /// Get a percent-decoded url value collected by a matched service, see
+    /// `Request::get_url_value`
+    pub fn get_url_value(&self, field: &str) -> Option<&Vec<String>> {
+        self.url_values.get(field)
+    }
+
+    /// Systems to run and url values to construct the `Request` with
+    pub(crate) fn into_parts(self) -> (Vec<&'r System<Data>>, HashMap<String, Vec<String>>) {
+        (self.systems().collect(), self.url_values)
+    }
+}
+
+impl<Data> fmt::Debug for Route<'_, Data> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths = self
+            .services()
+            .map(|service| service.path())
+            .collect::<Vec<_>>();
+
+        f.debug_struct("Route")
+            .field("pattern", &self.pattern)
+            .field("services", &paths)
+            .field("url_values", &self.url_values)
+            .finish()
+    }
+}
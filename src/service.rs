@@ -1,15 +1,48 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
-use crate::{websocket::WebsocketServiceExport, request::Request, response::Response};
+use tiny_http::Method;
+
+use crate::{
+    application::default_error_handler,
+    error::RouteError,
+    radix::{render, Segment},
+    request::Request,
+    response::{IntoResponse, Response},
+    router::child_segment,
+    sse::EventStreamServiceExport,
+    websocket::WebsocketServiceExport,
+};
 
 /// Service callback type used by application
 pub type SystemFn<Data> = fn(&mut Request, &Data) -> Command<Data>;
 
+/// Error returned by a `FallibleSystemFn`. Any error type can be converted with `?`, the
+/// application error handler may downcast it to produce a matching response.
+pub type SystemError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Service callback type that may fail, errors are passed to the application error handler. See
+/// `Application::with_error_handler`
+pub type FallibleSystemFn<Data> = fn(&mut Request, &Data) -> Result<Command<Data>, SystemError>;
+
+/// Service callback capturing its own state, used by reusable services configured at runtime.
+/// See `System::handler`
+pub type HandlerFn<Data> = Box<dyn Fn(&mut Request, &Data) -> Command<Data> + Send + Sync>;
+
+/// A single function of a `System`
+enum SystemEntry<Data> {
+    Infallible(SystemFn<Data>),
+    Fallible(FallibleSystemFn<Data>),
+    Handler(HandlerFn<Data>),
+}
+
 /// Describes the action of a `System`
 pub enum Command<Data> {
     /// Upgrade current connection to a websocket. This assumes the client is already trying to connect over Ws.
     Upgrade(Response, Box<dyn WebsocketServiceExport<Data>>),
 
+    /// Respond with a Server-Sent Events stream produced by the service on its own thread.
+    EventStream(Box<dyn EventStreamServiceExport<Data>>),
+
     /// Respond to request and don't step further services in tree.
     Respond(Response),
 
@@ -18,7 +51,13 @@ pub enum Command<Data> {
 }
 
 impl<Data> Command<Data> {
+    /// Respond with any value implementing `IntoResponse`
+    pub fn respond(value: impl IntoResponse) -> Self {
+        Command::Respond(value.into_response())
+    }
+
     /// Checks if command is `None`
+    #[allow(clippy::match_like_matches_macro)]
     pub fn is_none(&self) -> bool {
         match self {
             Command::None => true,
@@ -31,35 +70,100 @@ impl<Data> Command<Data> {
 /// multiple systems allows easy reuse of common middleware responsible for gathering information
 /// or parsing data.
 pub struct System<Data> {
-    collection: Vec<SystemFn<Data>>,
+    collection: Vec<SystemEntry<Data>>,
 }
 
 impl<Data> System<Data> {
     /// System constructor
     pub fn new(services: Vec<SystemFn<Data>>) -> Self {
         Self {
-            collection: services,
+            collection: services.into_iter().map(SystemEntry::Infallible).collect(),
         }
     }
 
     /// Constructs a system with only one `SystemFn`
     pub fn single(service: SystemFn<Data>) -> Self {
+        Self::new(vec![service])
+    }
+
+    /// Constructs a system with only one `FallibleSystemFn`
+    pub fn fallible(service: FallibleSystemFn<Data>) -> Self {
+        Self {
+            collection: vec![SystemEntry::Fallible(service)],
+        }
+    }
+
+    /// Constructs a system with only one closure, see `HandlerFn`
+    pub fn handler(
+        handler: impl Fn(&mut Request, &Data) -> Command<Data> + Send + Sync + 'static,
+    ) -> Self {
         Self {
-            collection: vec![service],
+            collection: vec![SystemEntry::Handler(Box::new(handler))],
         }
     }
 
-    /// Calls a systems underlying functions in order
+    /// Append a `SystemFn`, run after all previous functions
+    pub fn insert(mut self, service: SystemFn<Data>) -> Self {
+        self.collection.push(SystemEntry::Infallible(service));
+
+        self
+    }
+
+    /// Append a `FallibleSystemFn`, run after all previous functions
+    pub fn insert_fallible(mut self, service: FallibleSystemFn<Data>) -> Self {
+        self.collection.push(SystemEntry::Fallible(service));
+
+        self
+    }
+
+    /// Append a closure, run after all previous functions
+    pub fn insert_handler(
+        mut self,
+        handler: impl Fn(&mut Request, &Data) -> Command<Data> + Send + Sync + 'static,
+    ) -> Self {
+        self.collection
+            .push(SystemEntry::Handler(Box::new(handler)));
+
+        self
+    }
+
+    /// Number of functions in this system
+    pub fn len(&self) -> usize {
+        self.collection.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.collection.is_empty()
+    }
+
+    /// Calls a systems underlying functions in order. An error of a `FallibleSystemFn` is
+    /// answered with `default_error_handler`, see `try_call` to handle it instead.
     pub fn call(&self, request: &mut Request, data: &Data) -> Command<Data> {
+        match self.try_call(request, data) {
+            Ok(command) => command,
+            Err(e) => Command::respond(default_error_handler(request, data, e)),
+        }
+    }
+
+    /// Calls a systems underlying functions in order, stopping at the first error
+    pub fn try_call(
+        &self,
+        request: &mut Request,
+        data: &Data,
+    ) -> Result<Command<Data>, SystemError> {
         for system in self.collection.iter() {
-            let res = system(request, data);
+            let res = match system {
+                SystemEntry::Infallible(system) => system(request, data),
+                SystemEntry::Fallible(system) => system(request, data)?,
+                SystemEntry::Handler(system) => system(request, data),
+            };
 
             if !res.is_none() {
-                return res;
+                return Ok(res);
             }
         }
 
-        Command::None
+        Ok(Command::None)
     }
 }
 
@@ -70,6 +174,7 @@ impl<Data> From<SystemFn<Data>> for System<Data> {
 }
 
 /// Describes which url values to collect after a Service.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Param {
     /// Collect all url segments after attached Service into url value map.
     CollectAll(String),
@@ -78,7 +183,8 @@ pub enum Param {
     /// values.
     CollectMaybe(String, usize),
 
-    /// Collect some number of url segments into url value map. Run system only if enough values
+    /// Collect some number of url segments into url value map. Urls with fewer segments still
+    /// reach the service, without a url value.
     CollectExact(String, usize),
 
     /// Don't Collect any url segments.
@@ -90,7 +196,12 @@ pub struct Service<Data> {
     path: String,
     param: Param,
     systems: Option<System<Data>>,
+    methods: Option<Vec<Method>>,
+    name: Option<String>,
     children: HashMap<String, Box<Service<Data>>>,
+
+    /// Paths of children replaced by `insert_child`, reported by `Router::try_new`
+    replaced: Vec<String>,
 }
 
 impl<Data> Service<Data> {
@@ -100,7 +211,10 @@ impl<Data> Service<Data> {
             path: path.into(),
             param,
             systems: service.map(|inner| inner.into()),
+            methods: None,
+            name: None,
             children: HashMap::new(),
+            replaced: Vec::new(),
         }
     }
 
@@ -123,7 +237,10 @@ impl<Data> Service<Data> {
             path: path.into(),
             param: Param::None,
             systems: None,
+            methods: None,
+            name: None,
             children: HashMap::new(),
+            replaced: Vec::new(),
         }
     }
 
@@ -133,7 +250,10 @@ impl<Data> Service<Data> {
             path: path.into(),
             param: Param::None,
             systems: Some(callback.into()),
+            methods: None,
+            name: None,
             children: HashMap::new(),
+            replaced: Vec::new(),
         }
     }
 
@@ -143,7 +263,10 @@ impl<Data> Service<Data> {
             path: path.into(),
             param: Param::CollectExact(name, 1),
             systems: None,
+            methods: None,
+            name: None,
             children: HashMap::new(),
+            replaced: Vec::new(),
         }
     }
 
@@ -159,12 +282,250 @@ impl<Data> Service<Data> {
         self
     }
 
+    /// Methods this service responds to, listed by `routes` and `dump`. Requests are not
+    /// filtered by method, systems still check `Request::method` themselves.
+    pub fn insert_methods(mut self, methods: Vec<Method>) -> Self {
+        self.methods = Some(methods);
+
+        self
+    }
+
+    #[allow(clippy::borrowed_box)]
     pub fn get_child(&self, path: &str) -> Option<&Box<Service<Data>>> {
         self.children.get(path)
     }
 
+    /// Insert a child matched by the url segment equal to its path. Paths starting with `:`
+    /// match any single segment, collected as url value named by the rest of the path, and
+    /// paths starting with `*` match all remaining segments. Static paths take precedence, see
+    /// `Router`.
+    ///
+    /// A child already inserted at the same path is replaced, `Router::try_new` reports it.
     pub fn insert_child(&mut self, child: Service<Data>) {
-        self.children.insert(child.path.clone(), child.into());
+        if self.children.contains_key(&child.path) {
+            self.replaced.push(child.path.clone());
+        }
+
+        self.children.insert(child.path.clone(), Box::new(child));
+    }
+
+    /// Insert a child like `insert_child`, failing instead of replacing a child already inserted
+    /// at the same path
+    pub fn try_insert_child(&mut self, child: Service<Data>) -> Result<(), RouteError> {
+        if self.children.contains_key(&child.path) {
+            return Err(RouteError::DuplicatePath(child.path));
+        }
+
+        self.children.insert(child.path.clone(), Box::new(child));
+
+        Ok(())
+    }
+
+    /// Insert `service` at `prefix`, a path of one or more segments such as `/api/v2/admin`,
+    /// below this service. Missing services along the prefix are inserted with `with_path`. The
+    /// path of `service` is replaced by the last segment of the prefix, and `service` is merged
+    /// into a service already at the prefix, see `merge`. An empty prefix merges `service` into
+    /// this service.
+    ///
+    /// ```rust
+    /// # use simple_http::{request::Request, service::{Command, Service, System}, testing::TestClient};
+    /// fn users(_req: &mut Request, _data: &()) -> Command<()> {
+    ///     Command::respond("users")
+    /// }
+    ///
+    /// fn health(_req: &mut Request, _data: &()) -> Command<()> {
+    ///     Command::respond("ok")
+    /// }
+    ///
+    /// // Built by another module
+    /// let admin = Service::with_path("admin").fold(|s| {
+    ///     s.insert_child(Service::with_system("users", System::single(users)));
+    /// });
+    ///
+    /// let mut root = Service::root();
+    /// root.mount("/api/v2/health", Service::with_system("health", System::single(health)))
+    ///     .unwrap();
+    /// root.mount("/api/v2/admin", admin).unwrap();
+    ///
+    /// let client = TestClient::new(root, ());
+    /// let mut response = client.get("/api/v2/admin/users").send().unwrap();
+    /// assert_eq!(response.body_to_string().unwrap(), "users");
+    ///
+    /// let mut response = client.get("/api/v2/health").send().unwrap();
+    /// assert_eq!(response.body_to_string().unwrap(), "ok");
+    /// ```
+    pub fn mount(
+        &mut self,
+        prefix: &str,
+        mut service: Service<Data>,
+    ) -> Result<(), Vec<RouteError>> {
+        let mut segments = prefix.split('/').filter(|s| !s.is_empty()).peekable();
+
+        let mut node = self;
+        let mut pattern = String::new();
+
+        while let Some(segment) = segments.next() {
+            pattern = format!("{}/{}", pattern, segment);
+
+            if segments.peek().is_none() {
+                service.path = segment.to_string();
+
+                let Some(existing) = node.children.get_mut(segment) else {
+                    node.insert_child(service);
+
+                    return Ok(());
+                };
+
+                return existing.merge_at(service, pattern);
+            }
+
+            node = node
+                .children
+                .entry(segment.to_string())
+                .or_insert_with(|| Box::new(Service::with_path(segment)));
+        }
+
+        node.merge_at(service, pattern)
+    }
+
+    /// Merge the systems, `Param`, methods, name and children of `other` into this service,
+    /// recursively merging children inserted at the same path. The path of `other` is ignored.
+    ///
+    /// Services set conflicting values when both have systems, or different params, methods or
+    /// names. The values of this service are kept and every conflict is reported, the rest of
+    /// `other` is still merged.
+    ///
+    /// ```rust
+    /// # use simple_http::{error::RouteError, request::Request, service::{Command, Service, System}};
+    /// fn list(_req: &mut Request, _data: &()) -> Command<()> {
+    ///     Command::None
+    /// }
+    ///
+    /// let mut users = Service::with_path("users").fold(|s| {
+    ///     s.insert_child(Service::with_system("list", System::single(list)));
+    /// });
+    ///
+    /// let other = Service::with_path("users").fold(|s| {
+    ///     s.insert_child(Service::with_system("list", System::single(list)));
+    ///     s.insert_child(Service::with_system("search", System::single(list)));
+    /// });
+    ///
+    /// let errors = users.merge(other).unwrap_err();
+    /// assert!(matches!(&errors[..], [RouteError::MergeConflict { pattern, .. }] if pattern == "/list"));
+    /// assert!(users.get_child("search").is_some());
+    /// ```
+    pub fn merge(&mut self, other: Service<Data>) -> Result<(), Vec<RouteError>> {
+        self.merge_at(other, String::new())
+    }
+
+    fn merge_at(&mut self, other: Service<Data>, pattern: String) -> Result<(), Vec<RouteError>> {
+        let mut errors = Vec::new();
+
+        self.merge_into(other, pattern, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Merge `other` into this service at `pattern`, adding conflicts to `errors`
+    fn merge_into(&mut self, other: Service<Data>, pattern: String, errors: &mut Vec<RouteError>) {
+        let mut conflict = |reason| {
+            errors.push(RouteError::MergeConflict {
+                pattern: if pattern.is_empty() { "/" } else { &pattern }.to_string(),
+                reason,
+            });
+        };
+
+        match (&self.systems, other.systems) {
+            (Some(_), Some(_)) => conflict("both services have systems"),
+            (None, systems) => self.systems = systems,
+            _ => {}
+        }
+
+        match (&self.param, other.param) {
+            (_, Param::None) => {}
+            (Param::None, param) => self.param = param,
+            (param, other) if *param != other => conflict("the services collect different params"),
+            _ => {}
+        }
+
+        match (&self.methods, other.methods) {
+            (_, None) => {}
+            (None, methods) => self.methods = methods,
+            (Some(methods), Some(other)) if *methods != other => {
+                conflict("the services allow different methods")
+            }
+            _ => {}
+        }
+
+        match (&self.name, other.name) {
+            (_, None) => {}
+            (None, name) => self.name = name,
+            (Some(name), Some(other)) if *name != other => {
+                conflict("the services have different names")
+            }
+            _ => {}
+        }
+
+        self.replaced.extend(other.replaced);
+
+        // Sorted so conflicts are reported in the same order on every start
+        let mut children = other.children.into_iter().collect::<Vec<_>>();
+        children.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (path, child) in children {
+            match self.children.get_mut(&path) {
+                Some(existing) => {
+                    let pattern = format!("{}/{}", pattern, path);
+
+                    existing.merge_into(*child, pattern, errors);
+                }
+                None => {
+                    self.children.insert(path, child);
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Name used to build urls reaching this service, see `UrlTable`. Names must be unique
+    /// within a tree.
+    pub fn insert_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Direct children of this service, in no particular order
+    pub fn children(&self) -> impl Iterator<Item = &Service<Data>> {
+        self.children.values().map(|child| child.as_ref())
+    }
+
+    /// Children sorted by path, so walks of the tree visit them in the same order on every start
+    pub(crate) fn sorted_children(&self) -> Vec<&Service<Data>> {
+        let mut children = self.children().collect::<Vec<_>>();
+        children.sort_by(|a, b| a.path.cmp(&b.path));
+
+        children
+    }
+
+    pub(crate) fn replaced(&self) -> &[String] {
+        &self.replaced
+    }
+
+    /// Methods set with `insert_methods`, `None` if not set
+    pub fn methods(&self) -> Option<&[Method]> {
+        self.methods.as_deref()
     }
 
     pub fn systems(&self) -> &Option<System<Data>> {
@@ -174,4 +535,178 @@ impl<Data> Service<Data> {
     pub fn param(&self) -> &Param {
         &self.param
     }
+
+    /// Every reachable service of the tree below and including this one, sorted by pattern.
+    /// Children of `Param::CollectAll` services can't be reached and are left out.
+    ///
+    /// ```rust
+    /// # use simple_http::{request::Request, service::{Command, Param, Service, System}, Method};
+    /// fn list(_req: &mut Request, _data: &()) -> Command<()> {
+    ///     Command::None
+    /// }
+    ///
+    /// let root = Service::root().fold(|s| {
+    ///     s.insert_child(
+    ///         Service::with_system("users", System::single(list))
+    ///             .insert_methods(vec![Method::Get, Method::Post])
+    ///             .fold(|s| {
+    ///                 s.insert_child(
+    ///                     Service::with_param("posts", "post".to_string())
+    ///                         .insert_system(System::single(list).insert(list)),
+    ///                 );
+    ///             }),
+    ///     );
+    /// });
+    ///
+    /// let routes = root.routes();
+    /// let patterns = routes.iter().map(|r| r.pattern()).collect::<Vec<_>>();
+    /// assert_eq!(patterns, ["/", "/users", "/users/posts/:post"]);
+    ///
+    /// assert_eq!(routes[1].methods(), Some(&[Method::Get, Method::Post][..]));
+    /// assert_eq!(routes[2].param(), &Param::CollectExact("post".to_string(), 1));
+    /// assert_eq!(routes[2].systems(), 2);
+    ///
+    /// println!("{}", root.dump());
+    /// ```
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        let mut routes = Vec::new();
+        self.collect_routes(Vec::new(), &mut routes);
+
+        routes.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+
+        routes
+    }
+
+    /// One line per route returned by `routes` with its methods, pattern, `Param` and number of
+    /// systems, for logging the route table at startup
+    pub fn dump(&self) -> String {
+        let rows = self
+            .routes()
+            .iter()
+            .map(|route| route.columns())
+            .collect::<Vec<_>>();
+
+        let width = |column: usize| rows.iter().map(|row| row[column].len()).max().unwrap_or(0);
+        let widths = [width(0), width(1), width(2)];
+
+        rows.iter()
+            .map(|row| {
+                format!(
+                    "{:w0$}  {:w1$}  {:w2$}  {}",
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2]
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn collect_routes(&self, mut segments: Vec<Segment>, routes: &mut Vec<RouteInfo>) {
+        // Segments collected by this service, rendered like the patterns of `Router`
+        match &self.param {
+            Param::None => {}
+            Param::CollectExact(name, amount) | Param::CollectMaybe(name, amount) => {
+                segments.extend((0..*amount).map(|_| Segment::Param(name.clone())));
+            }
+            Param::CollectAll(name) => segments.push(Segment::CatchAll(name.clone())),
+        }
+
+        routes.push(RouteInfo {
+            pattern: render(&segments),
+            param: self.param.clone(),
+            methods: self.methods.clone(),
+            systems: self.systems.as_ref().map_or(0, |system| system.len()),
+            name: self.name.clone(),
+        });
+
+        // Children below a catch-all are unreachable, `Router::try_new` reports them
+        if let Some(Segment::CatchAll(_)) = segments.last() {
+            return;
+        }
+
+        for child in self.children.values() {
+            // Also unreachable, paths of children are a single segment
+            if child.path.contains('/') {
+                continue;
+            }
+
+            let mut segments = segments.clone();
+            segments.push(child_segment(&child.path));
+
+            child.collect_routes(segments, routes);
+        }
+    }
+}
+
+/// Description of a service in a tree, see `Service::routes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    pattern: String,
+    param: Param,
+    methods: Option<Vec<Method>>,
+    systems: usize,
+    name: Option<String>,
+}
+
+impl RouteInfo {
+    /// Path of the service from the root as matched by `Router`, with the segments collected by
+    /// `Param`s as `:name`, or `*name` for `Param::CollectAll`. `CollectMaybe` parameters are
+    /// shown with all of their segments.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn param(&self) -> &Param {
+        &self.param
+    }
+
+    /// Methods set with `Service::insert_methods`, `None` if not set
+    pub fn methods(&self) -> Option<&[Method]> {
+        self.methods.as_deref()
+    }
+
+    /// Number of functions in the `System` of the service, 0 if it has none
+    pub fn systems(&self) -> usize {
+        self.systems
+    }
+
+    /// Name set with `Service::insert_name`
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Methods, pattern, param and systems as displayed by `Service::dump`
+    fn columns(&self) -> [String; 4] {
+        let methods = match &self.methods {
+            Some(methods) => methods
+                .iter()
+                .map(|method| method.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => "*".to_string(),
+        };
+
+        let param = match &self.param {
+            Param::None => "-".to_string(),
+            param => format!("{:?}", param),
+        };
+
+        let systems = match self.systems {
+            1 => "1 system".to_string(),
+            systems => format!("{} systems", systems),
+        };
+
+        [methods, self.pattern.clone(), param, systems]
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.columns().join(" "))
+    }
 }
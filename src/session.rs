@@ -0,0 +1,505 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use tiny_http::StatusCode;
+
+use crate::{
+    cookie::{Cookie, SameSite},
+    percent::{decode, encode},
+    request::Request,
+    response::Response,
+    service::Command,
+};
+
+/// Key-value data of a session
+pub type SessionValues = HashMap<String, String>;
+
+/// Storage backend of `Sessions`. Stores are shared between all request threads.
+pub trait SessionStore: Send + Sync {
+    /// Load the values of session `id`, `None` if it doesn't exist or has expired
+    fn load(&self, id: &str) -> io::Result<Option<SessionValues>>;
+
+    /// Create or replace session `id`, expiring after `ttl`
+    fn store(&self, id: &str, values: &SessionValues, ttl: Duration) -> io::Result<()>;
+
+    /// Remove session `id`
+    fn remove(&self, id: &str) -> io::Result<()>;
+}
+
+/// Session attached to a request by `load`, see `Request::session`
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    id: Option<String>,
+    values: SessionValues,
+    modified: bool,
+    destroyed: bool,
+    previous_id: Option<String>,
+}
+
+impl Session {
+    /// Id of the session, `None` if the session has not been persisted yet
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), value.into());
+        self.modified = true;
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let value = self.values.remove(key);
+        self.modified |= value.is_some();
+
+        value
+    }
+
+    pub fn values(&self) -> &SessionValues {
+        &self.values
+    }
+
+    /// Remove all values, keeping the session id
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.modified = true;
+    }
+
+    /// Remove the session from the store and the client
+    pub fn destroy(&mut self) {
+        self.values.clear();
+        self.destroyed = true;
+    }
+
+    /// Move the session to a new id, this should be done whenever privileges change (e.g. on
+    /// login) to prevent session fixation.
+    pub fn renew(&mut self) {
+        if self.previous_id.is_none() {
+            self.previous_id = self.id.take();
+        }
+
+        self.id = None;
+        self.modified = true;
+    }
+}
+
+/// Session configuration, usually stored in the application data. `load` requires the data to
+/// implement `AsRef<Sessions>`.
+///
+/// ```rust,no_run
+/// # use simple_http::{application::Application, service::{Service, System}, session::{self, Sessions, MemoryStore}};
+/// let root = Service::root().insert_system(System::single(session::load::<Sessions>));
+///
+/// let app = Application::new("0.0.0.0:80", root, Sessions::new(MemoryStore::new()));
+/// ```
+#[derive(Clone)]
+pub struct Sessions {
+    store: Arc<dyn SessionStore>,
+    cookie_name: String,
+    ttl: Duration,
+    path: String,
+    secure: bool,
+    same_site: SameSite,
+}
+
+impl Sessions {
+    /// Constructs a session configuration with a default cookie named `session` and sessions
+    /// expiring after a day.
+    pub fn new(store: impl SessionStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie_name: "session".to_string(),
+            ttl: Duration::from_secs(60 * 60 * 24),
+            path: "/".to_string(),
+            secure: false,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    pub fn with_cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+
+        self
+    }
+
+    /// Sessions expire `ttl` after they were last modified
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+
+        self
+    }
+
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+
+        self
+    }
+
+    pub fn store(&self) -> &dyn SessionStore {
+        self.store.as_ref()
+    }
+
+    fn cookie(&self, value: impl Into<String>) -> Cookie {
+        Cookie::new(self.cookie_name.clone(), value)
+            .with_path(self.path.clone())
+            .with_http_only(true)
+            .with_secure(self.secure)
+            .with_same_site(self.same_site)
+    }
+
+    /// Persist changes to `session` and set the session cookie on `response`
+    fn save(&self, mut session: Session, response: Response) -> io::Result<Response> {
+        if let Some(previous_id) = &session.previous_id {
+            self.store.remove(previous_id)?;
+        }
+
+        if session.destroyed {
+            let Some(id) = session.id.or(session.previous_id) else {
+                return Ok(response);
+            };
+
+            self.store.remove(&id)?;
+
+            let removal = Cookie::removal(self.cookie_name.clone()).with_path(self.path.clone());
+
+            return Ok(response.with_cookie(removal));
+        }
+
+        // Empty sessions are only persisted once they have been used
+        if !session.modified || (session.id.is_none() && session.values.is_empty()) {
+            return Ok(response);
+        }
+
+        let id = match session.id.take() {
+            Some(id) => id,
+            None => generate_id()?,
+        };
+
+        self.store.store(&id, &session.values, self.ttl)?;
+
+        Ok(response.with_cookie(self.cookie(id).with_max_age(self.ttl)))
+    }
+}
+
+impl AsRef<Sessions> for Sessions {
+    fn as_ref(&self) -> &Sessions {
+        self
+    }
+}
+
+/// System loading the session identified by the session cookie into the request, see
+/// `Request::session`. Changes are persisted when the request is responded to. Failing to load
+/// or persist a session responds with `StatusCode(500)`.
+///
+/// ```rust
+/// # use simple_http::{request::Request, service::{Command, Service, System}, session::{self, MemoryStore, Sessions}, testing::TestClient};
+/// fn visit(req: &mut Request, _data: &Sessions) -> Command<Sessions> {
+///     let session = req.session_mut().unwrap();
+///     let visits = session.get("visits").map_or(0, |v| v.parse::<u32>().unwrap()) + 1;
+///     session.insert("visits", visits.to_string());
+///
+///     Command::respond(visits.to_string())
+/// }
+///
+/// fn login(req: &mut Request, _data: &Sessions) -> Command<Sessions> {
+///     let session = req.session_mut().unwrap();
+///     session.insert("user", "ada");
+///     session.renew();
+///
+///     Command::respond("welcome")
+/// }
+///
+/// fn logout(req: &mut Request, _data: &Sessions) -> Command<Sessions> {
+///     req.session_mut().unwrap().destroy();
+///
+///     Command::respond("bye")
+/// }
+///
+/// let root = Service::root()
+///     .insert_system(System::single(session::load::<Sessions>))
+///     .fold(|s| {
+///         s.insert_child(Service::with_system("visit", System::single(visit)));
+///         s.insert_child(Service::with_system("login", System::single(login)));
+///         s.insert_child(Service::with_system("logout", System::single(logout)));
+///     });
+///
+/// let client = TestClient::new(root, Sessions::new(MemoryStore::new()));
+/// let store = client.data().store();
+///
+/// let session_id = |set_cookie: &str| {
+///     let pair = set_cookie.split(';').next().unwrap();
+///     pair.strip_prefix("session=").unwrap().to_string()
+/// };
+///
+/// // The cookie is issued once the session is used
+/// let response = client.get("/visit").send().unwrap();
+/// let set_cookie = response.get_header("Set-Cookie").unwrap();
+/// assert!(set_cookie.contains("HttpOnly"));
+/// let id = session_id(set_cookie);
+///
+/// // Sending it back loads the session
+/// let cookie = format!("session={}", id);
+/// let mut response = client.get("/visit").header("Cookie", &cookie).send().unwrap();
+/// assert_eq!(response.body_to_string().unwrap(), "2");
+///
+/// // Renewing moves the values to a new id, the old one is no longer valid
+/// let response = client.get("/login").header("Cookie", &cookie).send().unwrap();
+/// let renewed = session_id(response.get_header("Set-Cookie").unwrap());
+/// assert_ne!(renewed, id);
+/// assert!(store.load(&id).unwrap().is_none());
+/// assert_eq!(store.load(&renewed).unwrap().unwrap()["visits"], "2");
+///
+/// let mut response = client.get("/visit").header("Cookie", &cookie).send().unwrap();
+/// assert_eq!(response.body_to_string().unwrap(), "1");
+///
+/// // Destroying removes the session from the store and the client
+/// let cookie = format!("session={}", renewed);
+/// let response = client.get("/logout").header("Cookie", &cookie).send().unwrap();
+/// assert!(response.get_header("Set-Cookie").unwrap().starts_with("session=;"));
+/// assert!(response.get_header("Set-Cookie").unwrap().contains("Max-Age=0"));
+/// assert!(store.load(&renewed).unwrap().is_none());
+/// ```
+pub fn load<Data: AsRef<Sessions>>(req: &mut Request, data: &Data) -> Command<Data> {
+    let sessions = data.as_ref();
+
+    let mut session = Session::default();
+
+    if let Some(id) = req
+        .get_cookie(&sessions.cookie_name)
+        .filter(|id| is_valid_id(id))
+    {
+        match sessions.store.load(id) {
+            Ok(Some(values)) => {
+                session.id = Some(id.to_string());
+                session.values = values;
+            }
+            Ok(None) => {}
+            Err(_) => return Command::Respond(Response::empty(StatusCode(500))),
+        }
+    }
+
+    req.set_session(session);
+
+    let sessions = sessions.clone();
+    req.on_response(move |req, response| {
+        let Some(session) = req.take_session() else {
+            return response;
+        };
+
+        sessions
+            .save(session, response)
+            .unwrap_or_else(|_| Response::empty(StatusCode(500)))
+    });
+
+    Command::None
+}
+
+/// Generates a random url safe session id
+fn generate_id() -> io::Result<String> {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes)?;
+
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Ids are sent by the client, only ids that could have been generated are passed to stores
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 43
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// In memory session store, sessions are lost on restart. Expired sessions are removed
+/// periodically when storing.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (SessionValues, Instant)>>,
+    last_purge: Mutex<Option<Instant>>,
+}
+
+impl MemoryStore {
+    /// Interval between removals of expired sessions
+    const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all expired sessions
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+
+        self.sessions
+            .lock()
+            .expect("Session store poisoned")
+            .retain(|_, (_, expires)| *expires > now);
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionValues>> {
+        let sessions = self.sessions.lock().expect("Session store poisoned");
+
+        Ok(sessions
+            .get(id)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(values, _)| values.clone()))
+    }
+
+    fn store(&self, id: &str, values: &SessionValues, ttl: Duration) -> io::Result<()> {
+        let purge = {
+            let mut last_purge = self.last_purge.lock().expect("Session store poisoned");
+
+            let due = last_purge.is_none_or(|last| last.elapsed() >= Self::PURGE_INTERVAL);
+            if due {
+                *last_purge = Some(Instant::now());
+            }
+
+            due
+        };
+
+        if purge {
+            self.purge_expired();
+        }
+
+        self.sessions
+            .lock()
+            .expect("Session store poisoned")
+            .insert(id.to_string(), (values.clone(), Instant::now() + ttl));
+
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        self.sessions
+            .lock()
+            .expect("Session store poisoned")
+            .remove(id);
+
+        Ok(())
+    }
+}
+
+/// Session store keeping one file per session in a directory. Expired sessions are removed when
+/// loaded, or with `FileStore::purge_expired`.
+pub struct FileStore {
+    directory: PathBuf,
+}
+
+impl FileStore {
+    /// Constructs a file store, creating `directory` if it doesn't exist
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self { directory })
+    }
+
+    /// Remove all expired sessions
+    pub fn purge_expired(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+
+            let Some(id) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if is_valid_id(id) {
+                self.load(id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.directory.join(id)
+    }
+}
+
+/// Session files contain the expiry in unix seconds on the first line, followed by one
+/// percent-encoded `key=value` pair per line.
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionValues>> {
+        let contents = match fs::read_to_string(self.path(id)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut lines = contents.lines();
+
+        let expires = lines
+            .next()
+            .and_then(|line| line.parse::<u64>().ok())
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+        if expires.is_none_or(|expires| expires <= SystemTime::now()) {
+            self.remove(id)?;
+
+            return Ok(None);
+        }
+
+        let values = lines
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (decode(key), decode(value)))
+            .collect();
+
+        Ok(Some(values))
+    }
+
+    fn store(&self, id: &str, values: &SessionValues, ttl: Duration) -> io::Result<()> {
+        let expires = (SystemTime::now() + ttl)
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let mut contents = format!("{}\n", expires.as_secs());
+        for (key, value) in values {
+            contents.push_str(&encode(key, is_plain_byte));
+            contents.push('=');
+            contents.push_str(&encode(value, is_plain_byte));
+            contents.push('\n');
+        }
+
+        // Write to a temporary file first so concurrent loads never see a partial session
+        let temporary = self.directory.join(format!(".{}.tmp", id));
+        fs::File::create(&temporary)?.write_all(contents.as_bytes())?;
+
+        fs::rename(temporary, self.path(id))
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Characters written to session files without encoding
+fn is_plain_byte(byte: u8) -> bool {
+    matches!(byte, 0x20..=0x7E) && byte != b'='
+}
@@ -0,0 +1,184 @@
+use std::{
+    io::{self, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{response::Response, stream::StreamWriter};
+
+/// Server-Sent Events writer. Every message is flushed to the client as soon as it is sent.
+///
+/// Sending fails with `io::ErrorKind::BrokenPipe` once the client disconnects, sending periodic
+/// `keep_alive` comments detects disconnected clients even when no events are produced.
+///
+/// ```rust
+/// # use std::{io::ErrorKind, time::Duration};
+/// # use simple_http::{request::Request, service::{Command, Service, System}, sse::EventStream, testing::TestClient};
+/// fn events(_req: &mut Request, _data: &()) -> Command<()> {
+///     let (mut stream, response) = EventStream::new();
+///
+///     std::thread::spawn(move || {
+///         stream.retry(Duration::from_millis(1500)).unwrap();
+///         stream.send(Some("update"), "line 1\nline 2", Some("7")).unwrap();
+///         stream.comment("still here").unwrap();
+///
+///         // Fields other than data must fit on one line
+///         let sent = stream.send(Some("a\nb"), "data", None);
+///         if sent.is_err_and(|e| e.kind() == ErrorKind::InvalidInput) {
+///             stream.keep_alive().unwrap();
+///         }
+///     });
+///
+///     Command::Respond(response)
+/// }
+///
+/// let client = TestClient::new(Service::with_system("root", System::single(events)), ());
+///
+/// let mut response = client.get("/").send().unwrap();
+/// assert_eq!(response.get_header("Content-Type"), Some("text/event-stream"));
+/// assert_eq!(
+///     response.body_to_string().unwrap(),
+///     "retry: 1500\n\nevent: update\nid: 7\ndata: line 1\ndata: line 2\n\n: still here\n\n:\n\n"
+/// );
+/// ```
+pub struct EventStream {
+    writer: StreamWriter,
+}
+
+impl EventStream {
+    /// Constructs an event stream and the response that must be responded with. The stream can
+    /// be moved to any thread, the response ends once it is dropped.
+    pub fn new() -> (Self, Response) {
+        let (writer, response) = Response::channel();
+
+        let response = response
+            .with_valid_header("Content-Type", "text/event-stream")
+            .with_valid_header("Cache-Control", "no-cache");
+
+        (Self { writer }, response)
+    }
+
+    /// Send an event. `event` sets the event type dispatched on the client, `id` is sent back by
+    /// the client in the `Last-Event-ID` header when reconnecting, see `Request::last_event_id`.
+    /// `data` may span multiple lines.
+    pub fn send(&mut self, event: Option<&str>, data: &str, id: Option<&str>) -> io::Result<()> {
+        let mut message = String::new();
+
+        if let Some(event) = event {
+            message.push_str(&format!("event: {}\n", single_line(event)?));
+        }
+
+        if let Some(id) = id {
+            if id.contains('\0') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Event ids may not contain null characters",
+                ));
+            }
+
+            message.push_str(&format!("id: {}\n", single_line(id)?));
+        }
+
+        for line in data.split("\r\n").flat_map(|l| l.split(['\r', '\n'])) {
+            message.push_str(&format!("data: {}\n", line));
+        }
+
+        message.push('\n');
+
+        self.write(&message)
+    }
+
+    /// Send a comment, ignored by clients. `text` may span multiple lines.
+    pub fn comment(&mut self, text: &str) -> io::Result<()> {
+        let mut message = String::new();
+
+        for line in text.split("\r\n").flat_map(|l| l.split(['\r', '\n'])) {
+            message.push_str(&format!(": {}\n", line));
+        }
+
+        message.push('\n');
+
+        self.write(&message)
+    }
+
+    /// Send an empty comment to keep the connection open through proxies and detect
+    /// disconnected clients
+    pub fn keep_alive(&mut self) -> io::Result<()> {
+        self.write(":\n\n")
+    }
+
+    /// Tell the client how long to wait before reconnecting once the connection is lost
+    pub fn retry(&mut self, delay: Duration) -> io::Result<()> {
+        self.write(&format!("retry: {}\n\n", delay.as_millis()))
+    }
+
+    fn write(&mut self, message: &str) -> io::Result<()> {
+        self.writer.write_all(message.as_bytes())?;
+        self.writer.flush()
+    }
+}
+
+/// Fields other than data can't be split across lines
+fn single_line(value: &str) -> io::Result<&str> {
+    if value.contains(['\r', '\n']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Event fields may not contain line breaks",
+        ));
+    }
+
+    Ok(value)
+}
+
+/// Event stream poll function. This is called repeatedly in an owned thread in
+/// `EventStreamService::run`
+pub type EventStreamFn<Data, State> = fn(&mut EventStream, &Data, State) -> Option<State>;
+
+/// Long running producer of a Server-Sent Events response with access to the application data,
+/// see `Command::EventStream`.
+pub trait EventStreamService<Data>
+where
+    Self: Sized + 'static,
+    Data: Sync + Send + 'static,
+{
+    type State: Sync + Send + 'static;
+
+    /// Returns the initial state of the `EventStreamService`
+    fn initial_state(&self) -> Self::State;
+
+    fn poll_fn(stream: &mut EventStream, data: &Data, state: Self::State) -> Option<Self::State>;
+
+    /// Runs the EventStreamService continuously in a loop until `poll_fn` returns `None`
+    fn run(self, data: Arc<Data>, mut stream: EventStream) {
+        let mut state = self.initial_state();
+        let callback = Self::poll_fn;
+
+        std::thread::spawn(move || loop {
+            state = if let Some(state) = callback(&mut stream, data.as_ref(), state) {
+                state
+            } else {
+                break;
+            }
+        });
+    }
+}
+
+/// Used to allow Boxing of `EventStreamService`, see `WebsocketServiceExport`
+pub trait EventStreamServiceExport<Data>
+where
+    Self: Sync + Send,
+{
+    /// Expected to run the run method of `EventStreamService`
+    fn run(self: Box<Self>, data: Arc<Data>, stream: EventStream);
+}
+
+impl<T, Data, State> EventStreamServiceExport<Data> for T
+where
+    T: Sync + Send + EventStreamService<Data, State = State>,
+    Data: Sync + Send + 'static,
+    State: Sync + Send + 'static,
+{
+    fn run(self: Box<Self>, data: Arc<Data>, stream: EventStream) {
+        EventStreamService::run(*self, data, stream)
+    }
+}
@@ -0,0 +1,830 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tiny_http::{Method, StatusCode};
+
+use crate::{
+    compression::{self, Encoding},
+    conditional,
+    mime::MimeTypes,
+    percent::{decode, encode, is_unreserved_byte},
+    range,
+    request::Request,
+    response::{Redirect, Response},
+    service::{Command, Param, Service, System},
+};
+
+/// Name of the url value holding the requested path in services constructed by
+/// `StaticFiles::service` and `EmbeddedFiles::service`
+const PATH_VALUE: &str = "path";
+
+/// How symbolic links below the root directory are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Respond with `StatusCode(404)` for paths containing symbolic links
+    Deny,
+
+    /// Follow symbolic links resolving to a path inside the root directory
+    WithinRoot,
+
+    /// Follow all symbolic links
+    Follow,
+}
+
+/// Missing paths answered with `StatusCode(404)` instead of the fallback file in single page
+/// application mode, see `StaticFiles::with_fallback`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FallbackExclusion {
+    /// Paths whose last segment has an extension, e.g. `/assets/missing.js`
+    Extensions,
+
+    /// Paths starting with the given segments below the service path, e.g. `api` excludes
+    /// `/api` and `/api/users`
+    Prefix(String),
+}
+
+/// Serves the files of a directory. Request paths are resolved segment by segment below the root
+/// directory, `..` segments, encoded separators and hidden files are rejected. Conditional and
+/// range requests are supported, see `conditional::apply` and `range::apply`, as are
+/// precompressed siblings of files, see `with_precompressed`.
+///
+/// ```rust,no_run
+/// # use simple_http::{application::Application, service::Service, static_files::StaticFiles};
+/// let root = Service::root().fold(|s| {
+///     s.insert_child(StaticFiles::new("public").service("assets"));
+/// });
+///
+/// let app = Application::new("0.0.0.0:80", root, ());
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index_files: Vec<String>,
+    symlinks: Symlinks,
+    hidden: bool,
+    listing: bool,
+    precompressed: Vec<Encoding>,
+    fallback: Option<String>,
+    fallback_exclusions: Vec<FallbackExclusion>,
+    mime_types: MimeTypes,
+}
+
+impl StaticFiles {
+    /// Constructs a configuration serving `root`, with `index.html` as index file and symbolic
+    /// links followed inside the root directory only.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            index_files: vec!["index.html".to_string()],
+            symlinks: Symlinks::WithinRoot,
+            hidden: false,
+            listing: false,
+            precompressed: Vec::new(),
+            fallback: None,
+            fallback_exclusions: vec![FallbackExclusion::Extensions],
+            mime_types: MimeTypes::new(),
+        }
+    }
+
+    /// Files served for requests to a directory, the first existing file is used
+    pub fn with_index_files(mut self, index_files: Vec<String>) -> Self {
+        self.index_files = index_files;
+
+        self
+    }
+
+    pub fn with_symlinks(mut self, symlinks: Symlinks) -> Self {
+        self.symlinks = symlinks;
+
+        self
+    }
+
+    /// Serve files and directories whose name starts with a `.`
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+
+        self
+    }
+
+    /// Respond to requests for directories without an index file with a listing of their
+    /// contents. The listing is sent as JSON if requested with `?format=json` or an `Accept`
+    /// header preferring `application/json`, as HTML otherwise. Entries are sorted by the `sort`
+    /// query value (`name`, `size` or `modified`) in the `order` (`asc` or `desc`), directories
+    /// first.
+    ///
+    /// ```rust
+    /// # use simple_http::{service::Service, static_files::StaticFiles, testing::TestClient, StatusCode};
+    /// let client = TestClient::new(
+    ///     Service::root().fold(|s| {
+    ///         let files = StaticFiles::new("examples/html")
+    ///             .with_index_files(Vec::new())
+    ///             .with_listing(true);
+    ///
+    ///         s.insert_child(files.service("html"));
+    ///     }),
+    ///     (),
+    /// );
+    ///
+    /// let mut response = client.get("/html/").send().unwrap();
+    /// assert_eq!(response.status_code(), StatusCode(200));
+    /// assert!(response.get_header("Content-Type").unwrap().starts_with("text/html"));
+    /// assert_eq!(response.get_header("Vary"), Some("Accept"));
+    ///
+    /// // Directories come first
+    /// let html = response.body_to_string().unwrap();
+    /// let directory = html.find("<a href=\"sub_folder/\">sub_folder/</a>").unwrap();
+    /// assert!(directory < html.find("<a href=\"home.html\">home.html</a>").unwrap());
+    ///
+    /// let mut response = client.get("/html/sub_folder/?format=json&order=desc").send().unwrap();
+    /// assert_eq!(response.get_header("Content-Type"), Some("application/json"));
+    /// assert_eq!(response.get_header("Vary"), Some("Accept"));
+    ///
+    /// let json = response.body_to_string().unwrap();
+    /// assert!(json.starts_with(r#"{"path":"/html/sub_folder/","entries":[{"name":"index.html","type":"file""#));
+    /// assert!(json.contains(r#"{"name":"file.txt","type":"file""#));
+    ///
+    /// let response = client.get("/html/sub_folder/").header("Accept", "application/json").send().unwrap();
+    /// assert_eq!(response.get_header("Content-Type"), Some("application/json"));
+    /// ```
+    pub fn with_listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+
+        self
+    }
+
+    /// Serve precompressed siblings of files to clients accepting their encoding, `.br` files for
+    /// `Encoding::Brotli` and `.gz` files for `Encoding::Gzip`. Equally accepted encodings are
+    /// preferred in the order of `encodings`, the plain file is served if no sibling matches.
+    ///
+    /// ```rust
+    /// # use std::fs;
+    /// # use simple_http::{compression::Encoding, service::Service, static_files::StaticFiles, testing::TestClient};
+    /// let dir = std::env::temp_dir().join(format!("precompressed-{}", std::process::id()));
+    /// fs::create_dir_all(&dir).unwrap();
+    /// fs::write(dir.join("app.js"), "plain").unwrap();
+    /// fs::write(dir.join("app.js.gz"), "gzipped").unwrap();
+    ///
+    /// let files = StaticFiles::new(&dir).with_precompressed(vec![Encoding::Brotli, Encoding::Gzip]);
+    ///
+    /// let mut root = Service::root();
+    /// root.insert_child(files.service("assets"));
+    /// let client = TestClient::new(root, ());
+    ///
+    /// let get = |accept_encoding: &str| {
+    ///     let mut response = client.get("/assets/app.js").header("Accept-Encoding", accept_encoding).send().unwrap();
+    ///     let encoding = response.get_header("Content-Encoding").map(|h| h.to_string());
+    ///
+    ///     assert_eq!(response.get_header("Content-Type"), Some("text/javascript; charset=utf-8"));
+    ///     assert_eq!(response.get_header("Vary"), Some("Accept-Encoding"));
+    ///
+    ///     (encoding, response.body_to_string().unwrap())
+    /// };
+    ///
+    /// assert_eq!(get("gzip"), (Some("gzip".into()), "gzipped".into()));
+    ///
+    /// // There is no brotli sibling, and the plain file is served to other clients
+    /// assert_eq!(get("br, gzip;q=0.5"), (Some("gzip".into()), "gzipped".into()));
+    /// assert_eq!(get("br"), (None, "plain".into()));
+    /// assert_eq!(get("identity"), (None, "plain".into()));
+    ///
+    /// fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn with_precompressed(mut self, encodings: Vec<Encoding>) -> Self {
+        self.precompressed = encodings;
+
+        self
+    }
+
+    /// Single page application mode, requests to missing paths are answered with the `fallback`
+    /// file relative to the root directory, usually `index.html`, so client side routing can
+    /// handle them. Paths matching a `FallbackExclusion` are still answered with
+    /// `StatusCode(404)`, by default paths with an extension such as missing assets.
+    ///
+    /// ```rust
+    /// # use simple_http::{service::Service, static_files::{FallbackExclusion, StaticFiles}, testing::TestClient, StatusCode};
+    /// let client = TestClient::new(
+    ///     Service::root().fold(|s| {
+    ///         let files = StaticFiles::new("examples/html")
+    ///             .with_fallback("index.html")
+    ///             .with_fallback_exclusions(vec![
+    ///                 FallbackExclusion::Extensions,
+    ///                 FallbackExclusion::Prefix("api".to_string()),
+    ///             ]);
+    ///
+    ///         s.insert_child(files.service("app"));
+    ///     }),
+    ///     (),
+    /// );
+    ///
+    /// let index = std::fs::read_to_string("examples/html/index.html").unwrap();
+    ///
+    /// let mut response = client.get("/app/settings/profile").send().unwrap();
+    /// assert_eq!(response.status_code(), StatusCode(200));
+    /// assert_eq!(response.get_header("Content-Type"), Some("text/html; charset=utf-8"));
+    /// assert_eq!(response.body_to_string().unwrap(), index);
+    ///
+    /// // Existing files are served as usual
+    /// let mut response = client.get("/app/sub_folder/file.txt").send().unwrap();
+    /// assert_ne!(response.body_to_string().unwrap(), index);
+    ///
+    /// let status = |url: &str| client.get(url).send().unwrap().status_code();
+    /// assert_eq!(status("/app/missing.js"), StatusCode(404));
+    /// assert_eq!(status("/app/api"), StatusCode(404));
+    /// assert_eq!(status("/app/api/users"), StatusCode(404));
+    /// assert_eq!(status("/app/apis"), StatusCode(200));
+    /// ```
+    pub fn with_fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.fallback = Some(fallback.into());
+
+        self
+    }
+
+    /// Replace the paths excluded from the fallback file, see `with_fallback`
+    pub fn with_fallback_exclusions(mut self, exclusions: Vec<FallbackExclusion>) -> Self {
+        self.fallback_exclusions = exclusions;
+
+        self
+    }
+
+    /// Content types of served files, the built-in `MimeTypes` by default
+    pub fn with_mime_types(mut self, mime_types: MimeTypes) -> Self {
+        self.mime_types = mime_types;
+
+        self
+    }
+
+    /// Constructs a service at `path` serving the files below it
+    pub fn service<Data: 'static>(self, path: impl Into<String>) -> Service<Data> {
+        files_service(path, move |req, segments| self.serve(req, segments))
+    }
+
+    /// Respond to `req` with the file at the path made of the url `segments` below the root
+    /// directory, for use in custom systems.
+    ///
+    /// Segments that could leave the root directory are answered with `StatusCode(404)`,
+    /// whether they were sent percent-encoded or not.
+    ///
+    /// ```rust
+    /// # use simple_http::{service::Service, static_files::StaticFiles, testing::TestClient, StatusCode};
+    /// let client = TestClient::new(
+    ///     Service::root().fold(|s| s.insert_child(StaticFiles::new("examples/html").service("html"))),
+    ///     (),
+    /// );
+    ///
+    /// let status = |url: &str| client.get(url).send().unwrap().status_code();
+    ///
+    /// assert_eq!(status("/html/../Cargo.toml"), StatusCode(404));
+    /// assert_eq!(status("/html/..%2F..%2FCargo.toml"), StatusCode(404));
+    /// assert_eq!(status("/html/%2e%2e/Cargo.toml"), StatusCode(404));
+    /// assert_eq!(status("/html/.%2e/Cargo.toml"), StatusCode(404));
+    /// assert_eq!(status("/html/sub_folder%2Ffile.txt"), StatusCode(404));
+    /// assert_eq!(status("/html/missing.txt"), StatusCode(404));
+    ///
+    /// // Directories are redirected to their url with a trailing `/`
+    /// let response = client.get("/html/sub_folder?tab=1").send().unwrap();
+    /// assert_eq!(response.status_code(), StatusCode(301));
+    /// assert_eq!(response.get_header("Location"), Some("/html/sub_folder/?tab=1"));
+    ///
+    /// let mut response = client.get("/html/sub_folder/file.txt").send().unwrap();
+    /// assert_eq!(response.status_code(), StatusCode(200));
+    /// assert_eq!(response.get_header("Content-Type"), Some("text/plain; charset=utf-8"));
+    /// assert!(!response.body_to_string().unwrap().is_empty());
+    ///
+    /// let response = client.get("/html/style.css").send().unwrap();
+    /// assert_eq!(response.get_header("Content-Type"), Some("text/css; charset=utf-8"));
+    /// assert_eq!(client.post("/html/style.css").send().unwrap().status_code(), StatusCode(405));
+    /// ```
+    pub fn serve(&self, req: &Request, segments: &[String]) -> Response {
+        if let Some(response) = method_not_allowed(req) {
+            return response;
+        }
+
+        let Some(mut path) = self.resolve(segments) else {
+            return Response::empty(StatusCode(404));
+        };
+
+        if !path.exists() {
+            match self.fallback_path(segments) {
+                Some(fallback) => path = fallback,
+                None => return Response::empty(StatusCode(404)),
+            }
+        }
+
+        if path.is_dir() {
+            if let Some(redirect) = directory_redirect(req) {
+                return redirect;
+            }
+
+            let index = self
+                .index_files
+                .iter()
+                .map(|index| path.join(index))
+                .find(|index| index.is_file());
+
+            match index {
+                Some(index) => path = index,
+                None if self.listing && self.allows_links(&path) => {
+                    return self.list(req, &path, !segments.is_empty());
+                }
+                None => return Response::empty(StatusCode(404)),
+            }
+        }
+
+        if !self.allows_links(&path) {
+            return Response::empty(StatusCode(404));
+        }
+
+        // A precompressed sibling removed since it was found falls back to the requested file
+        let opened = match self.precompressed_variant(req, &path) {
+            Some((variant, encoding)) => File::open(variant)
+                .map(|file| (file, Some(encoding)))
+                .or_else(|_| File::open(&path).map(|file| (file, None))),
+            None => File::open(&path).map(|file| (file, None)),
+        };
+
+        let (response, encoding) = match opened {
+            // The type of the requested file, not of a precompressed sibling
+            Ok((file, encoding)) => (self.mime_types.apply(&path, Response::file(file)), encoding),
+            Err(e) => return Response::empty(error_status(&e)),
+        };
+
+        let response = match encoding {
+            Some(encoding) => response.with_valid_header("Content-Encoding", encoding.name()),
+            None => response,
+        };
+
+        let response = if self.precompressed.is_empty() {
+            response
+        } else {
+            compression::add_vary(response)
+        };
+
+        // Answering preconditions first avoids preparing ranges for a `StatusCode(304)`
+        range::apply(req, conditional::apply(req, response))
+    }
+
+    /// The best precompressed sibling of `path` accepted by the client and its encoding, `None`
+    /// if there is none
+    fn precompressed_variant(&self, req: &Request, path: &Path) -> Option<(PathBuf, Encoding)> {
+        let accept_encoding = accept_encoding(req)?;
+
+        let variant = |encoding: &Encoding| {
+            let extension = match encoding {
+                Encoding::Brotli => "br",
+                Encoding::Gzip => "gz",
+                Encoding::Deflate => return None,
+            };
+
+            let mut variant = path.as_os_str().to_owned();
+            variant.push(".");
+            variant.push(extension);
+
+            let variant = PathBuf::from(variant);
+            (variant.is_file() && self.allows_links(&variant)).then_some(variant)
+        };
+
+        let variants = self
+            .precompressed
+            .iter()
+            .filter_map(|encoding| Some((*encoding, variant(encoding)?)))
+            .collect::<Vec<_>>();
+
+        let available = variants
+            .iter()
+            .map(|(encoding, _)| *encoding)
+            .collect::<Vec<_>>();
+
+        let encoding = compression::negotiate(accept_encoding, &available)?;
+
+        variants
+            .into_iter()
+            .find(|(available, _)| *available == encoding)
+            .map(|(encoding, variant)| (variant, encoding))
+    }
+
+    /// Listing of the directory at `path`, entries hidden or not allowed by the symlink policy
+    /// are left out
+    fn list(&self, req: &Request, path: &Path, has_parent: bool) -> Response {
+        let read_dir = match fs::read_dir(path) {
+            Ok(read_dir) => read_dir,
+            Err(e) => return Response::empty(error_status(&e)),
+        };
+
+        let mut entries = Vec::new();
+        for entry in read_dir.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let entry_path = entry.path();
+
+            if (!self.hidden && name.starts_with('.')) || !self.allows_links(&entry_path) {
+                continue;
+            }
+
+            let Ok(metadata) = fs::metadata(&entry_path) else {
+                continue;
+            };
+
+            entries.push(ListingEntry {
+                name,
+                directory: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified: metadata.modified().ok(),
+            });
+        }
+
+        let (url_path, query) = split_query(req.url());
+        let query = parse_query(query.unwrap_or(""));
+        let value = |key: &str| {
+            query
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let sort = match value("sort") {
+            Some("size") => "size",
+            Some("modified") => "modified",
+            _ => "name",
+        };
+        let descending = value("order") == Some("desc");
+
+        entries.sort_by(|a, b| {
+            let order = match sort {
+                "size" => a.size.cmp(&b.size),
+                "modified" => a.modified.cmp(&b.modified),
+                _ => a.name.cmp(&b.name),
+            };
+            let order = if descending { order.reverse() } else { order };
+
+            b.directory.cmp(&a.directory).then(order)
+        });
+
+        let json = match value("format") {
+            Some(format) => format == "json",
+            None => req
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Accept"))
+                .is_some_and(|h| prefers_json(h.value.as_str())),
+        };
+
+        let title = decode(url_path);
+
+        let response = if json {
+            Response::bytes(listing_json(&title, &entries))
+                .with_valid_header("Content-Type", "application/json")
+        } else {
+            Response::html(listing_html(&title, &entries, has_parent, sort, descending))
+        };
+
+        // Both formats are served at the same url, caches must key them by `Accept`
+        response.with_valid_header("Vary", "Accept")
+    }
+
+    /// Joins the `segments` onto the root directory, `None` if a segment could escape it
+    fn resolve(&self, segments: &[String]) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+
+        for segment in path_segments(segments) {
+            if segment.contains(['/', '\\', '\0']) || (!self.hidden && segment.starts_with('.')) {
+                return None;
+            }
+
+            let mut components = Path::new(&segment).components();
+            if !matches!(components.next(), Some(Component::Normal(_)))
+                || components.next().is_some()
+            {
+                return None;
+            }
+
+            path.push(segment);
+        }
+
+        Some(path)
+    }
+
+    /// The fallback file answering requests to the missing path made of `segments`, `None` if
+    /// there is no fallback or the path is excluded from it
+    fn fallback_path(&self, segments: &[String]) -> Option<PathBuf> {
+        let fallback = self.fallback.as_ref()?;
+        let segments = path_segments(segments);
+
+        let excluded = self
+            .fallback_exclusions
+            .iter()
+            .any(|exclusion| match exclusion {
+                FallbackExclusion::Extensions => segments
+                    .last()
+                    .is_some_and(|segment| Path::new(segment).extension().is_some()),
+                FallbackExclusion::Prefix(prefix) => {
+                    let prefix = prefix.split('/').filter(|s| !s.is_empty());
+
+                    prefix.clone().count() <= segments.len()
+                        && prefix.zip(&segments).all(|(a, b)| a == *b)
+                }
+            });
+
+        (!excluded).then(|| self.root.join(fallback))
+    }
+
+    /// Checks the symbolic links on the way to `path` against the configured policy
+    fn allows_links(&self, path: &Path) -> bool {
+        match self.symlinks {
+            Symlinks::Follow => true,
+            Symlinks::WithinRoot => {
+                let (Ok(root), Ok(path)) = (fs::canonicalize(&self.root), fs::canonicalize(path))
+                else {
+                    return false;
+                };
+
+                path.starts_with(root)
+            }
+            Symlinks::Deny => {
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    return false;
+                };
+
+                let mut current = self.root.clone();
+                relative.components().all(|component| {
+                    current.push(component);
+
+                    fs::symlink_metadata(&current).is_ok_and(|m| !m.file_type().is_symlink())
+                })
+            }
+        }
+    }
+}
+
+/// Service at `path` collecting the segments below it and answering requests with `serve`
+pub(crate) fn files_service<Data: 'static>(
+    path: impl Into<String>,
+    serve: impl Fn(&Request, &[String]) -> Response + Send + Sync + 'static,
+) -> Service<Data> {
+    let system = System::handler(move |req: &mut Request, _: &Data| {
+        let segments = req.get_url_value(PATH_VALUE).cloned().unwrap_or_default();
+
+        Command::Respond(serve(req, &segments))
+    });
+
+    Service::with_system(path, system)
+        .insert_param(Param::CollectAll(PATH_VALUE.to_string()))
+        .insert_methods(vec![Method::Get, Method::Head])
+}
+
+/// `StatusCode(405)` for requests to files with methods other than `GET` and `HEAD`
+pub(crate) fn method_not_allowed(req: &Request) -> Option<Response> {
+    if *req.method() == Method::Get || *req.method() == Method::Head {
+        return None;
+    }
+
+    let response = Response::empty(StatusCode(405)).with_valid_header("Allow", "GET, HEAD");
+
+    Some(response)
+}
+
+/// Redirect to the url of `req` with a trailing `/` when a directory is requested without one,
+/// relative links in index files only work below the directory
+pub(crate) fn directory_redirect(req: &Request) -> Option<Response> {
+    let (url_path, query) = split_query(req.url());
+    if url_path.ends_with('/') {
+        return None;
+    }
+
+    let location = match query {
+        Some(query) => format!("{}/?{}", url_path, query),
+        None => format!("{}/", url_path),
+    };
+
+    Some(Response::redirect(Redirect::MovedPermanently, &location))
+}
+
+/// Value of the `Accept-Encoding` header of `req`
+pub(crate) fn accept_encoding<'r>(req: &'r Request) -> Option<&'r str> {
+    req.headers()
+        .iter()
+        .find(|h| h.field.equiv("Accept-Encoding"))
+        .map(|h| h.value.as_str())
+}
+
+/// Non-empty `segments`, url values are already percent-decoded by the router
+pub(crate) fn path_segments(segments: &[String]) -> Vec<&str> {
+    segments
+        .iter()
+        .map(|segment| segment.as_str())
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+/// Splits a url into its path and query
+pub(crate) fn split_query(url: &str) -> (&str, Option<&str>) {
+    match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url, None),
+    }
+}
+
+fn error_status(error: &io::Error) -> StatusCode {
+    match error.kind() {
+        io::ErrorKind::NotFound => StatusCode(404),
+        io::ErrorKind::PermissionDenied => StatusCode(403),
+        _ => StatusCode(500),
+    }
+}
+
+/// Directory entry shown in a listing
+struct ListingEntry {
+    name: String,
+    directory: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+fn listing_html(
+    title: &str,
+    entries: &[ListingEntry],
+    has_parent: bool,
+    sort: &str,
+    descending: bool,
+) -> String {
+    let title = escape_html(title);
+
+    // Column headers sort by their column, toggling the order of the current column
+    let header = |column: &str, label: &str| {
+        let order = if column == sort && !descending {
+            "desc"
+        } else {
+            "asc"
+        };
+
+        format!(
+            "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>",
+            column, order, label
+        )
+    };
+
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Index of {0}</title>
+</head>
+<body>
+<h1>Index of {0}</h1>
+<table>
+<tr>{1}{2}{3}</tr>
+"#,
+        title,
+        header("name", "Name"),
+        header("size", "Size"),
+        header("modified", "Modified"),
+    );
+
+    if has_parent {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+
+    for entry in entries {
+        let suffix = if entry.directory { "/" } else { "" };
+        let size = if entry.directory {
+            "-".to_string()
+        } else {
+            format_size(entry.size)
+        };
+        let modified = entry
+            .modified
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default();
+
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            encode(&entry.name, is_unreserved_byte),
+            suffix,
+            escape_html(&entry.name),
+            suffix,
+            size,
+            modified
+        ));
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    html
+}
+
+/// Listing as a JSON object, modification times are in unix seconds
+fn listing_json(title: &str, entries: &[ListingEntry]) -> String {
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let modified = entry
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs().to_string())
+                .unwrap_or_else(|| "null".to_string());
+
+            format!(
+                "{{\"name\":\"{}\",\"type\":\"{}\",\"size\":{},\"modified\":{}}}",
+                escape_json(&entry.name),
+                if entry.directory { "directory" } else { "file" },
+                entry.size,
+                modified
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        "{{\"path\":\"{}\",\"entries\":[{}]}}",
+        escape_json(title),
+        entries
+    )
+}
+
+/// `application/json` is accepted with a higher quality than `text/html`
+fn prefers_json(accept: &str) -> bool {
+    let quality = |media_type: &str| {
+        accept
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let name = parts.next()?.trim();
+
+                (name.eq_ignore_ascii_case(media_type)).then(|| {
+                    parts
+                        .filter_map(|param| param.trim().strip_prefix("q="))
+                        .find_map(|q| q.trim().parse::<f32>().ok())
+                        .unwrap_or(1.0)
+                })
+            })
+            .next()
+            .unwrap_or(0.0)
+    };
+
+    quality("application/json") > quality("text/html")
+}
+
+/// Decoded `key=value` pairs of a query string
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,185 @@
+use std::{
+    io::{self, Read, Write},
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    time::SystemTime,
+};
+
+use tiny_http::{Header, StatusCode};
+
+/// Size at which buffered data is sent as a chunk without an explicit flush
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Number of chunks buffered before writes block, limiting memory use when the client reads
+/// slower than data is produced.
+const CHANNEL_BOUND: usize = 16;
+
+/// Data sent by a `StreamWriter` to the body of its response, an error aborts the response
+pub(crate) type Chunk = io::Result<Vec<u8>>;
+
+/// Writer producing the body of a streaming response, see `Response::stream` and
+/// `Response::channel`.
+///
+/// Data is buffered and sent once `CHUNK_SIZE` bytes are written or on `flush`. Dropping the
+/// writer flushes remaining data and ends the response. Writes fail with
+/// `io::ErrorKind::BrokenPipe` once the client disconnects. Use `abort` to end the response
+/// without completing it.
+pub struct StreamWriter {
+    sender: SyncSender<Chunk>,
+    buffer: Vec<u8>,
+}
+
+impl StreamWriter {
+    /// Constructs a writer and the receiving end used as a response body
+    pub(crate) fn new() -> (Self, Receiver<Chunk>) {
+        let (sender, receiver) = sync_channel(CHANNEL_BOUND);
+
+        let writer = Self {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        };
+
+        (writer, receiver)
+    }
+
+    /// End the response without completing it, so the client can tell the body is truncated.
+    /// Data not flushed yet is discarded.
+    pub fn abort(mut self, error: io::Error) {
+        self.buffer.clear();
+
+        let _ = self.sender.send(Err(error));
+    }
+
+    /// Sender of the same stream, used to abort it once the writer is owned by an encoder
+    pub(crate) fn sender(&self) -> SyncSender<Chunk> {
+        self.sender.clone()
+    }
+}
+
+impl Write for StreamWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+
+        self.sender
+            .send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client disconnected"))
+    }
+}
+
+impl Drop for StreamWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Reads the chunks of a streaming body as a continuous reader, used when a response can't be
+/// sent with chunked transfer encoding.
+pub(crate) struct ChannelReader {
+    receiver: Receiver<Chunk>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    pub(crate) fn new(receiver: Receiver<Chunk>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.chunk.len() {
+            let Ok(chunk) = self.receiver.recv() else {
+                return Ok(0);
+            };
+
+            self.chunk = chunk?;
+            self.position = 0;
+        }
+
+        let remaining = &self.chunk[self.position..];
+        let amount = remaining.len().min(buf.len());
+        buf[..amount].copy_from_slice(&remaining[..amount]);
+        self.position += amount;
+
+        Ok(amount)
+    }
+}
+
+/// Writes a response with chunked transfer encoding, flushing every chunk as soon as it is
+/// received so clients see data as it is produced.
+///
+/// An aborted stream is never terminated with the last chunk. The connection can't be closed
+/// through the writer, so a malformed chunk is sent instead, clients treat it as a failed
+/// transfer and close the connection.
+pub(crate) fn write_chunked(
+    mut writer: impl Write,
+    status_code: StatusCode,
+    headers: &[Header],
+    receiver: Receiver<Chunk>,
+) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\n",
+        status_code.0,
+        status_code.default_reason_phrase()
+    )?;
+
+    if !headers.iter().any(|h| h.field.equiv("Date")) {
+        write!(
+            writer,
+            "Date: {}\r\n",
+            httpdate::fmt_http_date(SystemTime::now())
+        )?;
+    }
+
+    // Framing is decided here, framing headers set by systems would corrupt the response
+    for header in headers.iter().filter(|h| {
+        !h.field.equiv("Content-Length")
+            && !h.field.equiv("Transfer-Encoding")
+            && !h.field.equiv("Connection")
+    }) {
+        write!(writer, "{}: {}\r\n", header.field, header.value)?;
+    }
+
+    write!(writer, "Transfer-Encoding: chunked\r\n\r\n")?;
+    writer.flush()?;
+
+    for chunk in receiver.iter() {
+        let chunk = match chunk {
+            Ok(chunk) if chunk.is_empty() => continue,
+            Ok(chunk) => chunk,
+            Err(e) => {
+                write!(writer, "x\r\n")?;
+                writer.flush()?;
+
+                return Err(e);
+            }
+        };
+
+        write!(writer, "{:x}\r\n", chunk.len())?;
+        writer.write_all(&chunk)?;
+        write!(writer, "\r\n")?;
+        writer.flush()?;
+    }
+
+    write!(writer, "0\r\n\r\n")?;
+    writer.flush()
+}
@@ -0,0 +1,290 @@
+use std::{
+    io::{self, Read},
+    sync::Arc,
+};
+
+use tiny_http::{Header, Method, StatusCode};
+
+use crate::{
+    application::{default_error_handler, dispatch, Dispatch, ErrorHandlerFn},
+    error::{HeaderError, RouteError},
+    request::Request,
+    response::{make_header, Response},
+    router::Router,
+    service::Service,
+};
+
+/// Dispatches requests through a service tree in process, with the same routing, systems and
+/// response hooks as `Application::run` but without binding a port. Meant for tests.
+///
+/// ```rust
+/// # use simple_http::{request::Request, service::{Command, Service, System}, testing::TestClient, StatusCode};
+/// fn hello(req: &mut Request, _data: &()) -> Command<()> {
+///     let name = req.get_url_value("name").and_then(|v| v.first()).cloned();
+///
+///     Command::respond(format!("Hello {}", name.unwrap_or_default()))
+/// }
+///
+/// let root = Service::root().fold(|s| {
+///     s.insert_child(
+///         Service::with_param("hello", "name".to_string()).insert_system(System::single(hello)),
+///     );
+/// });
+///
+/// let client = TestClient::new(root, ());
+///
+/// let mut response = client.get("/hello/world").send().unwrap();
+/// assert_eq!(response.status_code(), StatusCode(200));
+/// assert_eq!(response.body_to_string().unwrap(), "Hello world");
+///
+/// let response = client.get("/missing").send().unwrap();
+/// assert_eq!(response.status_code(), StatusCode(404));
+/// ```
+pub struct TestClient<Data = ()>
+where
+    Data: Send + Sync,
+{
+    router: Router<Data>,
+    data: Arc<Data>,
+    error_handler: ErrorHandlerFn<Data>,
+}
+
+impl<Data> TestClient<Data>
+where
+    Data: Send + Sync + 'static,
+{
+    /// Constructs a client dispatching requests to `root` with `data`. Unlike `Application::new`
+    /// the tree is compiled with `Router::new`, problems are logged and the routes compiled
+    /// first are kept. Use `try_new` to check that the tree is accepted by `Application::new`.
+    pub fn new(root: Service<Data>, data: Data) -> Self {
+        Self::with_router(Router::new(root), data)
+    }
+
+    /// Constructs a client like `new`, failing on the conflicting or unreachable routes
+    /// `Application::new` refuses, see `Router::try_new`
+    ///
+    /// ```rust
+    /// # use simple_http::{error::RouteError, service::Service, testing::TestClient};
+    /// let root: Service<()> = Service::root().fold(|s| {
+    ///     s.insert_child(Service::with_path("users"));
+    ///     s.insert_child(Service::with_path("users"));
+    /// });
+    ///
+    /// let errors = TestClient::try_new(root, ()).err().unwrap();
+    /// assert_eq!(errors, [RouteError::DuplicatePath("/users".to_string())]);
+    ///
+    /// assert!(TestClient::try_new(Service::<()>::root(), ()).is_ok());
+    /// ```
+    pub fn try_new(root: Service<Data>, data: Data) -> Result<Self, Vec<RouteError>> {
+        Ok(Self::with_router(Router::try_new(root)?, data))
+    }
+
+    fn with_router(router: Router<Data>, data: Data) -> Self {
+        Self {
+            router,
+            data: Arc::new(data),
+            error_handler: default_error_handler,
+        }
+    }
+
+    /// Replace the error handler, see `Application::with_error_handler`
+    pub fn with_error_handler(mut self, handler: ErrorHandlerFn<Data>) -> Self {
+        self.error_handler = handler;
+
+        self
+    }
+
+    pub fn data(&self) -> &Data {
+        &self.data
+    }
+
+    /// Constructs a request to `url`, which starts with a `/` and may include a query string
+    pub fn request(&self, method: Method, url: impl Into<String>) -> TestRequest<'_, Data> {
+        TestRequest {
+            client: self,
+            method,
+            url: url.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn get(&self, url: impl Into<String>) -> TestRequest<'_, Data> {
+        self.request(Method::Get, url)
+    }
+
+    pub fn head(&self, url: impl Into<String>) -> TestRequest<'_, Data> {
+        self.request(Method::Head, url)
+    }
+
+    pub fn post(&self, url: impl Into<String>) -> TestRequest<'_, Data> {
+        self.request(Method::Post, url)
+    }
+
+    pub fn put(&self, url: impl Into<String>) -> TestRequest<'_, Data> {
+        self.request(Method::Put, url)
+    }
+
+    pub fn patch(&self, url: impl Into<String>) -> TestRequest<'_, Data> {
+        self.request(Method::Patch, url)
+    }
+
+    pub fn delete(&self, url: impl Into<String>) -> TestRequest<'_, Data> {
+        self.request(Method::Delete, url)
+    }
+
+    /// Routes and dispatches a request like `Application::run`. Websocket upgrades are answered
+    /// with the upgrade response without running the websocket service.
+    fn dispatch(
+        &self,
+        method: Method,
+        url: String,
+        headers: Vec<Header>,
+        body: Vec<u8>,
+    ) -> TestResponse {
+        let Some(route) = self.router.route(&url) else {
+            return TestResponse::new(&method, Response::empty(StatusCode(404)));
+        };
+
+        let (systems, url_values) = route.into_parts();
+
+        let body_length = Some(body.len());
+        let mut body = io::Cursor::new(body);
+
+        let mut request = Request::new(
+            method.clone(),
+            url,
+            headers,
+            &mut body,
+            body_length,
+            url_values,
+            self.router.urls().clone(),
+        );
+
+        let dispatched = dispatch(&systems, &mut request, &self.data, self.error_handler);
+
+        let response = match dispatched {
+            Dispatch::Respond(response) => response,
+            Dispatch::Upgrade(response, _) => response,
+        };
+
+        TestResponse::new(&method, response)
+    }
+}
+
+/// Request sent by a `TestClient`, header errors are reported once the request is sent
+pub struct TestRequest<'c, Data>
+where
+    Data: Send + Sync,
+{
+    client: &'c TestClient<Data>,
+    method: Method,
+    url: String,
+    headers: Vec<Header>,
+    body: Vec<u8>,
+    error: Option<HeaderError>,
+}
+
+impl<Data> TestRequest<'_, Data>
+where
+    Data: Send + Sync + 'static,
+{
+    /// Insert a header, an invalid header is reported when the request is sent
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        match make_header(key, value) {
+            Ok(header) => self.headers.push(header),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+
+        self
+    }
+
+    /// Dispatch the request through the service tree of the client
+    pub fn send(self) -> Result<TestResponse, HeaderError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        Ok(self
+            .client
+            .dispatch(self.method, self.url, self.headers, self.body))
+    }
+}
+
+/// Response produced by a `TestClient`. Streaming bodies are read as they are produced, reading
+/// them blocks until the producing thread writes or ends the stream.
+pub struct TestResponse {
+    status_code: StatusCode,
+    headers: Vec<Header>,
+    body: Box<dyn Read + Send>,
+    body_length: Option<usize>,
+}
+
+impl TestResponse {
+    fn new(method: &Method, response: Response) -> Self {
+        let (status_code, headers, body, body_length) = response.into_parts();
+
+        // Responses to `HEAD` requests are sent without a body
+        let body: Box<dyn Read + Send> = match method {
+            Method::Head => Box::new(io::empty()),
+            _ => body,
+        };
+
+        Self {
+            status_code,
+            headers,
+            body,
+            body_length,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    pub fn headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    /// Get the value of the first header named `key`
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(key))
+            .map(|h| h.value.as_str())
+    }
+
+    /// Length of the body if known before reading it, as sent in `Content-Length`
+    pub fn body_length(&self) -> Option<usize> {
+        self.body_length
+    }
+
+    pub fn as_reader(&mut self) -> &mut dyn Read {
+        &mut self.body
+    }
+
+    pub fn body_to_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let mut body_buf = Vec::new();
+
+        self.body.read_to_end(&mut body_buf)?;
+
+        Ok(body_buf)
+    }
+
+    pub fn body_to_string(&mut self) -> io::Result<String> {
+        let mut body_buf = String::new();
+
+        self.body.read_to_string(&mut body_buf)?;
+
+        Ok(body_buf)
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    error::{RouteError, UrlError},
+    percent::{encode, is_unreserved_byte},
+    radix::Segment,
+    router::child_segment,
+    service::{Param, Service},
+};
+
+/// Builds urls reaching the services of a tree named with `Service::insert_name`, so paths are
+/// only spelled out once in the tree. The `Router` of an application keeps one, systems reach it
+/// through `Request::url_for`.
+///
+/// Values fill the segments collected by the `Param` of the named service and of its parents, and
+/// children with paths starting with `:` or `*`, in order from the root. Each value is one
+/// percent-encoded segment, a `/` in a value does not start a new segment. Empty values, `.` and
+/// `..` are rejected. Paths of services are percent-encoded the same way.
+///
+/// ```rust
+/// # use simple_http::{router::Router, service::{Param, Service}, urls::UrlTable};
+/// let root: Service<()> = Service::root().fold(|s| {
+///     s.insert_child(
+///         Service::with_path("users")
+///             .insert_param(Param::CollectExact("id".to_string(), 1))
+///             .insert_name("user")
+///             .fold(|s| {
+///                 s.insert_child(Service::with_path(":post").insert_name("post"));
+///             }),
+///     );
+///     s.insert_child(
+///         Service::with_path("my files")
+///             .insert_param(Param::CollectAll("path".to_string()))
+///             .insert_name("files"),
+///     );
+/// });
+///
+/// let urls = UrlTable::new(&root);
+///
+/// let url = urls.url_for("user").value("id", "42").query("tab", "a&b").build();
+/// assert_eq!(url.unwrap(), "/users/42?tab=a%26b");
+///
+/// let url = urls.url_for("post").value("id", "42").value("post", "hello world").build();
+/// assert_eq!(url.unwrap(), "/users/42/hello%20world");
+///
+/// let url = urls.url_for("files").values("path", ["css", "site.css"]).build();
+/// assert_eq!(url.unwrap(), "/my%20files/css/site.css");
+/// assert_eq!(urls.url_for("files").build().unwrap(), "/my%20files");
+///
+/// // The shape of the route is checked
+/// assert!(urls.url_for("user").build().is_err());
+/// assert!(urls.url_for("user").values("id", ["1", "2"]).build().is_err());
+/// assert!(urls.url_for("missing").build().is_err());
+///
+/// // Values must be usable as a segment
+/// assert!(urls.url_for("user").value("id", "..").build().is_err());
+/// assert!(urls.url_for("files").values("path", ["css", ""]).build().is_err());
+///
+/// // Built urls route back to the same values
+/// let router = Router::new(root);
+/// let url = urls.url_for("post").value("id", "a/b").value("post", "hello world").build();
+/// let route = router.route(&url.unwrap()).unwrap();
+/// assert_eq!(route.get_url_value("id"), Some(&vec!["a/b".to_string()]));
+/// assert_eq!(route.get_url_value("post"), Some(&vec!["hello world".to_string()]));
+///
+/// let url = urls.url_for("files").values("path", ["css", "site.css"]).build();
+/// let route = router.route(&url.unwrap()).unwrap();
+/// assert_eq!(route.pattern(), "/my%20files/*path");
+/// ```
+#[derive(Debug, Default)]
+pub struct UrlTable {
+    routes: HashMap<String, Vec<Part>>,
+}
+
+/// Segments of the url of a named service
+#[derive(Debug, Clone)]
+enum Part {
+    /// Percent-encoded path of a service
+    Static(String),
+
+    /// Between `min` and `max` values, any amount above `min` if `max` is `None`
+    Values {
+        name: String,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// Parts of the child segments of `router::child_segment`, so urls are built for the same
+/// patterns the router matches
+impl From<Segment> for Part {
+    fn from(segment: Segment) -> Self {
+        match segment {
+            Segment::Static(path) => Part::Static(path),
+            Segment::Param(name) => Part::Values {
+                name,
+                min: 1,
+                max: Some(1),
+            },
+            Segment::CatchAll(name) => Part::Values {
+                name,
+                min: 0,
+                max: None,
+            },
+        }
+    }
+}
+
+impl UrlTable {
+    /// Collects the named services of the tree below `root`. Names used more than once are logged
+    /// and the first service in path order keeps the name.
+    pub fn new<Data>(root: &Service<Data>) -> Self {
+        let mut errors = Vec::new();
+        let table = Self::collect(root, &mut errors);
+
+        for e in errors {
+            log::warn!("Ignoring route name: {}", e);
+        }
+
+        table
+    }
+
+    /// Collects the named services below `root` like `new`, adding names used more than once to
+    /// `errors`
+    pub(crate) fn collect<Data>(root: &Service<Data>, errors: &mut Vec<RouteError>) -> Self {
+        let mut table = Self::default();
+
+        table.walk(root, Vec::new(), errors);
+
+        table
+    }
+
+    /// Start building the url of the service named `name`
+    pub fn url_for(&self, name: &str) -> UrlBuilder<'_> {
+        UrlBuilder {
+            table: self,
+            name: name.to_string(),
+            values: HashMap::new(),
+            query: Vec::new(),
+        }
+    }
+
+    /// Whether a service is named `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.routes.contains_key(name)
+    }
+
+    fn walk<Data>(
+        &mut self,
+        service: &Service<Data>,
+        mut parts: Vec<Part>,
+        errors: &mut Vec<RouteError>,
+    ) {
+        // Nothing can follow a child whose path starts with `*`
+        let after_catch_all = matches!(parts.last(), Some(Part::Values { max: None, .. }));
+
+        if let Some(name) = service.name() {
+            let mut own = parts.clone();
+
+            let values = match service.param() {
+                Param::None => None,
+                Param::CollectExact(name, amount) => Some((name, *amount, Some(*amount))),
+                Param::CollectMaybe(name, amount) => Some((name, 0, Some(*amount))),
+                Param::CollectAll(name) => Some((name, 0, None)),
+            };
+
+            if let Some((name, min, max)) = values.filter(|_| !after_catch_all) {
+                own.push(Part::Values {
+                    name: name.clone(),
+                    min,
+                    max,
+                });
+            }
+
+            if self.routes.contains_key(name) {
+                errors.push(RouteError::DuplicateName(name.to_string()));
+            } else {
+                self.routes.insert(name.to_string(), own);
+            }
+        }
+
+        if after_catch_all {
+            return;
+        }
+
+        // Segments collected by this service come before those of its children
+        match service.param() {
+            Param::None => {}
+            Param::CollectExact(name, amount) | Param::CollectMaybe(name, amount) => {
+                parts.push(Part::Values {
+                    name: name.clone(),
+                    min: *amount,
+                    max: Some(*amount),
+                });
+            }
+            Param::CollectAll(_) => return,
+        }
+
+        // Duplicate names resolve the same way on every start
+        for child in service.sorted_children() {
+            // Unreachable, `Router::try_new` reports it
+            if child.path().contains('/') {
+                continue;
+            }
+
+            let mut parts = parts.clone();
+            parts.push(child_segment(child.path()).into());
+
+            self.walk(child, parts, errors);
+        }
+    }
+}
+
+/// Url of a named service under construction, see `UrlTable::url_for`
+pub struct UrlBuilder<'t> {
+    table: &'t UrlTable,
+    name: String,
+    values: HashMap<String, VecDeque<String>>,
+    query: Vec<(String, String)>,
+}
+
+impl UrlBuilder<'_> {
+    /// Add one segment for the parameter `name`. Parameters collecting several segments take
+    /// their values in the order they were added.
+    pub fn value(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.values
+            .entry(name.to_string())
+            .or_default()
+            .push_back(value.into());
+
+        self
+    }
+
+    /// Add a segment for the parameter `name` per value, see `value`
+    pub fn values<I>(mut self, name: &str, values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.values
+            .entry(name.to_string())
+            .or_default()
+            .extend(values.into_iter().map(Into::into));
+
+        self
+    }
+
+    /// Append a query string parameter, in the order they were added
+    pub fn query(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.query.push((key.to_string(), value.into()));
+
+        self
+    }
+
+    /// The path and query string of the url. Fails if the service is unknown or if the values
+    /// do not fit the segments it is reached at.
+    pub fn build(mut self) -> Result<String, UrlError> {
+        let Some(parts) = self.table.routes.get(&self.name) else {
+            return Err(UrlError::UnknownRoute(self.name));
+        };
+
+        let mut url = String::new();
+
+        for part in parts {
+            match part {
+                Part::Static(segment) => {
+                    url.push('/');
+                    url.push_str(segment);
+                }
+                Part::Values { name, min, max } => {
+                    let values = self.values.entry(name.clone()).or_default();
+                    let amount = max.map_or(values.len(), |max| max.min(values.len()));
+
+                    if amount < *min {
+                        return Err(UrlError::MissingValues {
+                            route: self.name,
+                            name: name.clone(),
+                        });
+                    }
+
+                    for value in values.drain(..amount) {
+                        // Such segments would not reach the service
+                        if matches!(value.as_str(), "" | "." | "..") {
+                            return Err(UrlError::InvalidValue {
+                                route: self.name,
+                                name: name.clone(),
+                                value,
+                            });
+                        }
+
+                        url.push('/');
+                        url.push_str(&encode(&value, is_unreserved_byte));
+                    }
+                }
+            }
+        }
+
+        if let Some((name, _)) = self.values.iter().find(|(_, values)| !values.is_empty()) {
+            return Err(UrlError::UnexpectedValues {
+                route: self.name,
+                name: name.clone(),
+            });
+        }
+
+        if url.is_empty() {
+            url.push('/');
+        }
+
+        for (i, (key, value)) in self.query.iter().enumerate() {
+            url.push(if i == 0 { '?' } else { '&' });
+            url.push_str(&encode(key, is_unreserved_byte));
+            url.push('=');
+            url.push_str(&encode(value, is_unreserved_byte));
+        }
+
+        Ok(url)
+    }
+}
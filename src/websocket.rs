@@ -1,3 +1,4 @@
+#[allow(unused_imports)]
 use std::{io::Read, sync::{Arc, mpsc}};
 
 use tiny_http::ReadWrite;
@@ -10,6 +11,7 @@ type ReadWriteBoxed = Box<dyn ReadWrite + Send>;
 /// `WebsocketService::run`
 
 /// A safe unoptimized mask application.
+#[allow(clippy::empty_line_after_doc_comments)]
 #[inline]
 fn apply_mask_fallback(buf: &mut [u8], mask: &[u8]) {
     for (i, byte) in buf.iter_mut().enumerate() {
@@ -95,6 +97,7 @@ impl Websocket {
         Self { inner }
     }
 
+    #[allow(clippy::ptr_arg)]
     pub fn read_exact(&mut self, buf: &mut Vec<u8>) -> Result<(), std::io::Error> {
         self.inner.read_exact(buf)
     }
@@ -133,6 +136,7 @@ impl Websocket {
     }
 
     /// Wait for next frame, blocking
+    #[allow(clippy::needless_question_mark)]
     pub fn next_frame(&mut self) -> Result<RawFrame, Error> {
         let fin_and_op = self.read_u8();
 